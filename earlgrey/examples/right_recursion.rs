@@ -0,0 +1,55 @@
+// Benchmark parsing long right-recursive inputs.
+// Run with: cargo run --release --example right_recursion
+use std::time::Instant;
+
+fn build_grammar() -> earlgrey::Grammar {
+    // pow := num '^' pow | num ;
+    // set := pow '=' set | pow ;
+    earlgrey::GrammarBuilder::default()
+        .nonterm("set")
+        .nonterm("pow")
+        .terminal("num", |n| n.chars().all(|c| c.is_ascii_digit()))
+        .terminal("^", |n| n == "^")
+        .terminal("=", |n| n == "=")
+        .rule("set", &["pow", "=", "set"])
+        .rule("set", &["pow"])
+        .rule("pow", &["num", "^", "pow"])
+        .rule("pow", &["num"])
+        .into_grammar("set")
+        .expect("Bad Gramar")
+}
+
+fn input(operator: &str, len: usize) -> Vec<String> {
+    let mut tokens = vec!["1".to_string()];
+    for _ in 1..len {
+        tokens.push(operator.to_string());
+        tokens.push("1".to_string());
+    }
+    tokens
+}
+
+fn main() {
+    let parser = earlgrey::EarleyParser::new(build_grammar());
+    let mut ev = earlgrey::EarleyForest::new(|_, _| 1);
+    ev.action("set -> pow = set", |n| n[0] + n[2]);
+    ev.action("set -> pow", |n| n[0]);
+    ev.action("pow -> num ^ pow", |n| n[0] + n[2]);
+    ev.action("pow -> num", |n| n[0]);
+
+    for operator in ["^", "="] {
+        println!("=== Right recursion on '{}' ===", operator);
+        for len in [1000, 2000, 4000, 8000] {
+            let tokens = input(operator, len);
+            let start = Instant::now();
+            let trees = parser.parse(tokens.iter()).expect("Parse failed");
+            let parse_time = start.elapsed();
+            let start = Instant::now();
+            assert_eq!(ev.eval(&trees), Ok(len));
+            let eval_time = start.elapsed();
+            println!(
+                "{:>5} operands: parse {:>10.2?}  eval {:>10.2?}",
+                len, parse_time, eval_time
+            );
+        }
+    }
+}
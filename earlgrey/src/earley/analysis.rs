@@ -0,0 +1,405 @@
+#![deny(warnings)]
+
+use super::grammar::{Grammar, Symbol};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Problems found by `Grammar::lint`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Lint {
+    /// Non-terminal used in a rule but that has no rules of its own
+    Undefined(String),
+    /// Non-terminal that can't be derived from the start symbol
+    Unreachable(String),
+    /// Non-terminal that can never derive a string of terminals
+    Unproductive(String),
+    /// Non-terminals that derive themselves (A ->+ A), ie: infinite ambiguity
+    Cycle(Vec<String>),
+    /// Terminal `GrammarBuilder::import` shared with a different terminal of
+    /// the same name, the imported one was dropped
+    TerminalCollision(String),
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lint::Undefined(name) => write!(f, "Undefined non-terminal: {}", name),
+            Lint::Unreachable(name) => write!(f, "Unreachable non-terminal: {}", name),
+            Lint::Unproductive(name) => write!(f, "Unproductive non-terminal: {}", name),
+            Lint::Cycle(names) => write!(f, "Cycle: {}", names.join(" -> ")),
+            Lint::TerminalCollision(name) => write!(f, "Terminal name collision: {}", name),
+        }
+    }
+}
+
+impl Grammar {
+    /// Marks the end of input in FOLLOW sets
+    pub const EOF: &'static str = "<EOF>";
+
+    // Non-terminals mentioned by the grammar, either as heads or in bodies
+    fn nonterminals(&self) -> HashSet<&str> {
+        let mut nonterms = HashSet::from([self.start.as_str()]);
+        for rule in self.rules() {
+            nonterms.insert(rule.head.as_str());
+            nonterms.extend(rule.spec.iter().filter_map(|s| match &**s {
+                Symbol::NonTerm(name) => Some(name.as_str()),
+                Symbol::Term(_, _) => None,
+            }));
+        }
+        nonterms
+    }
+
+    fn reachable(&self) -> HashSet<&str> {
+        let mut reachable = HashSet::from([self.start.as_str()]);
+        let mut pending = vec![self.start.as_str()];
+        while let Some(head) = pending.pop() {
+            for rule in self.rules().iter().filter(|r| r.head == head) {
+                for symbol in &rule.spec {
+                    if let Symbol::NonTerm(name) = &**symbol
+                        && reachable.insert(name)
+                    {
+                        pending.push(name);
+                    }
+                }
+            }
+        }
+        reachable
+    }
+
+    // A rule head is productive if all symbols in some rule are productive.
+    // Keep marking heads until no new productive symbols are found.
+    fn productive(&self) -> HashSet<&str> {
+        let mut productive = HashSet::new();
+        loop {
+            let prev_len = productive.len();
+            for rule in self.rules() {
+                if rule.spec.iter().all(|s| match &**s {
+                    Symbol::NonTerm(name) => productive.contains(name.as_str()),
+                    Symbol::Term(_, _) => true,
+                }) {
+                    productive.insert(rule.head.as_str());
+                }
+            }
+            if prev_len == productive.len() {
+                return productive;
+            }
+        }
+    }
+
+    // A -> α B β where α and β are nullable means A can derive just B
+    fn unit_derivations(&self) -> HashMap<&str, HashSet<&str>> {
+        let mut derivations: HashMap<_, HashSet<_>> = HashMap::new();
+        for rule in self.rules() {
+            for (idx, symbol) in rule.spec.iter().enumerate() {
+                let Symbol::NonTerm(name) = &**symbol else {
+                    continue;
+                };
+                let rest_nullable = rule.spec.iter().enumerate().all(|(i, s)| {
+                    i == idx || matches!(&**s, Symbol::NonTerm(n) if self.is_nullable(n))
+                });
+                if rest_nullable {
+                    derivations
+                        .entry(rule.head.as_str())
+                        .or_default()
+                        .insert(name.as_str());
+                }
+            }
+        }
+        derivations
+    }
+
+    // Groups of non-terminals that derive each other through unit derivations
+    fn cycles(&self) -> Vec<Vec<String>> {
+        let derivations = self.unit_derivations();
+        let reach = |from: &str| {
+            let mut reached = HashSet::new();
+            let mut pending = vec![from];
+            while let Some(head) = pending.pop() {
+                for &next in derivations.get(head).into_iter().flatten() {
+                    if reached.insert(next) {
+                        pending.push(next);
+                    }
+                }
+            }
+            reached
+        };
+        let mut nonterms: Vec<_> = derivations.keys().copied().collect();
+        nonterms.sort();
+        let mut in_cycle = HashSet::new();
+        let mut cycles = Vec::new();
+        for head in nonterms {
+            let reached = reach(head);
+            if in_cycle.contains(head) || !reached.contains(head) {
+                continue;
+            }
+            let mut cycle: Vec<_> = reached
+                .into_iter()
+                .filter(|&other| reach(other).contains(head))
+                .collect();
+            cycle.sort();
+            in_cycle.extend(cycle.iter().copied());
+            cycles.push(cycle.into_iter().map(String::from).collect());
+        }
+        cycles
+    }
+
+    /// Check the grammar for symbols that are undefined, unreachable or
+    /// unproductive, for cycles (A ->+ A) and for terminal name collisions.
+    pub fn lint(&self) -> Vec<Lint> {
+        let defined: HashSet<_> = self.rules().iter().map(|r| r.head.as_str()).collect();
+        let reachable = self.reachable();
+        let productive = self.productive();
+        let mut lints = Vec::new();
+        for name in self.nonterminals() {
+            if !defined.contains(name) {
+                lints.push(Lint::Undefined(name.to_string()));
+            } else if !productive.contains(name) {
+                lints.push(Lint::Unproductive(name.to_string()));
+            }
+            if !reachable.contains(name) {
+                lints.push(Lint::Unreachable(name.to_string()));
+            }
+        }
+        lints.extend(self.cycles().into_iter().map(Lint::Cycle));
+        lints.extend(self.collisions.iter().cloned().map(Lint::TerminalCollision));
+        lints.sort();
+        lints
+    }
+
+    /// Terminals that can start a string derived from each non-terminal.
+    /// The empty string isn't included, check `is_nullable` for that.
+    pub fn first_sets(&self) -> HashMap<String, HashSet<String>> {
+        let mut first: HashMap<String, HashSet<String>> = self
+            .nonterminals()
+            .into_iter()
+            .map(|name| (name.to_string(), HashSet::new()))
+            .collect();
+        loop {
+            let mut changed = false;
+            for rule in self.rules() {
+                for symbol in &rule.spec {
+                    let new_first = match &**symbol {
+                        Symbol::Term(name, _) => HashSet::from([name.clone()]),
+                        Symbol::NonTerm(name) => first[name].clone(),
+                    };
+                    let head_first = first.get_mut(&rule.head).unwrap();
+                    let prev_len = head_first.len();
+                    head_first.extend(new_first);
+                    changed |= prev_len != head_first.len();
+                    if !matches!(&**symbol, Symbol::NonTerm(n) if self.is_nullable(n)) {
+                        break;
+                    }
+                }
+            }
+            if !changed {
+                return first;
+            }
+        }
+    }
+
+    /// Terminals that can come right after each non-terminal. The end of
+    /// input is represented by `Grammar::EOF`.
+    pub fn follow_sets(&self) -> HashMap<String, HashSet<String>> {
+        let first = self.first_sets();
+        let mut follow: HashMap<String, HashSet<String>> = first
+            .keys()
+            .map(|name| (name.clone(), HashSet::new()))
+            .collect();
+        follow
+            .get_mut(&self.start)
+            .unwrap()
+            .insert(Self::EOF.to_string());
+        loop {
+            let mut changed = false;
+            for rule in self.rules() {
+                for (idx, symbol) in rule.spec.iter().enumerate() {
+                    let Symbol::NonTerm(name) = &**symbol else {
+                        continue;
+                    };
+                    // Collect FIRST of what follows, up to a non-nullable symbol
+                    let mut new_follow = HashSet::new();
+                    let mut rest_nullable = true;
+                    for next in &rule.spec[idx + 1..] {
+                        match &**next {
+                            Symbol::Term(term, _) => {
+                                new_follow.insert(term.clone());
+                            }
+                            Symbol::NonTerm(nonterm) => new_follow.extend(first[nonterm].clone()),
+                        }
+                        if !matches!(&**next, Symbol::NonTerm(n) if self.is_nullable(n)) {
+                            rest_nullable = false;
+                            break;
+                        }
+                    }
+                    if rest_nullable {
+                        new_follow.extend(follow[&rule.head].clone());
+                    }
+                    let symbol_follow = follow.get_mut(name).unwrap();
+                    let prev_len = symbol_follow.len();
+                    symbol_follow.extend(new_follow);
+                    changed |= prev_len != symbol_follow.len();
+                }
+            }
+            if !changed {
+                return follow;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::grammar::{Grammar, GrammarBuilder};
+    use super::Lint;
+    use std::collections::HashSet;
+
+    fn set(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn lint_clean() {
+        // E -> E + T | T ; T -> n | ( E )
+        let g = GrammarBuilder::default()
+            .nonterm("E")
+            .nonterm("T")
+            .terminal("n", |n| n == "n")
+            .terminal("+", |n| n == "+")
+            .terminal("(", |n| n == "(")
+            .terminal(")", |n| n == ")")
+            .rule("E", &["E", "+", "T"])
+            .rule("E", &["T"])
+            .rule("T", &["n"])
+            .rule("T", &["(", "E", ")"])
+            .into_grammar("E")
+            .unwrap();
+        assert_eq!(g.lint(), vec![]);
+    }
+
+    #[test]
+    fn lint_problems() {
+        let g = GrammarBuilder::default()
+            .nonterm("S")
+            .nonterm("A")
+            .nonterm("B")
+            .nonterm("C")
+            .nonterm("L")
+            .nonterm("U")
+            .nonterm("X")
+            .terminal("a", |n| n == "a")
+            .rule("S", &["A"])
+            .rule("S", &["L", "X"])
+            .rule("A", &["B", "C"])
+            .rule("A", &["a"])
+            .rule("B", &["A"])
+            .rule("B", &[])
+            .rule("C", &[])
+            .rule("L", &["L", "a"])
+            .rule("U", &["a"])
+            .into_grammar("S")
+            .unwrap();
+        assert_eq!(
+            g.lint(),
+            vec![
+                Lint::Undefined("X".to_string()),
+                Lint::Unreachable("U".to_string()),
+                Lint::Unproductive("L".to_string()),
+                Lint::Cycle(vec!["A".to_string(), "B".to_string()]),
+            ]
+        );
+        assert_eq!(g.lint()[3].to_string(), "Cycle: A -> B");
+    }
+
+    #[test]
+    fn lint_terminal_collision() {
+        let sum = |plus: &str| {
+            GrammarBuilder::default()
+                .nonterm("S")
+                .pattern("n", "[0-9]")
+                .pattern("+", plus)
+                .rule("S", &["n", "+", "n"])
+                .into_grammar("S")
+                .unwrap()
+        };
+        let lists = GrammarBuilder::default()
+            .nonterm("L")
+            .terminal("x", |x| x == "x")
+            .rule("L", &["x"])
+            .into_grammar("L")
+            .unwrap();
+        // Same patterns and the same grammar twice are fine
+        let g = GrammarBuilder::default()
+            .nonterm("S")
+            .import("a", &sum(r"\+"))
+            .import("b", &sum(r"\+"))
+            .import("c", &lists)
+            .import("d", &lists)
+            .rule("S", &["a.S", "b.S", "c.L", "d.L"])
+            .into_grammar("S")
+            .unwrap();
+        assert_eq!(g.lint(), vec![]);
+        // Imported terminals are dropped for ours when they don't match alike
+        let g = GrammarBuilder::default()
+            .nonterm("S")
+            .terminal("x", |x| x == "x")
+            .import("a", &sum(r"\+"))
+            .import("b", &sum(r"\+|plus"))
+            .import("c", &lists)
+            .rule("S", &["a.S", "b.S", "c.L"])
+            .into_grammar("S")
+            .unwrap();
+        assert_eq!(
+            g.lint(),
+            vec![
+                Lint::TerminalCollision("+".to_string()),
+                Lint::TerminalCollision("x".to_string()),
+            ]
+        );
+        assert_eq!(g.lint()[1].to_string(), "Terminal name collision: x");
+        // Collisions carry over when importing the grammar elsewhere
+        let outer = GrammarBuilder::default()
+            .nonterm("T")
+            .import("x", &g)
+            .rule("T", &["x.S"])
+            .into_grammar("T")
+            .unwrap();
+        assert_eq!(outer.lint(), g.lint());
+    }
+
+    #[test]
+    fn first_follow() {
+        // E -> T E' ; E' -> + T E' | <e> ; T -> F T' ; T' -> * F T' | <e>
+        // F -> ( E ) | id
+        let g = GrammarBuilder::default()
+            .nonterm("E")
+            .nonterm("E'")
+            .nonterm("T")
+            .nonterm("T'")
+            .nonterm("F")
+            .terminal("+", |n| n == "+")
+            .terminal("*", |n| n == "*")
+            .terminal("(", |n| n == "(")
+            .terminal(")", |n| n == ")")
+            .terminal("id", |n| n == "id")
+            .rule("E", &["T", "E'"])
+            .rule("E'", &["+", "T", "E'"])
+            .rule("E'", &[])
+            .rule("T", &["F", "T'"])
+            .rule("T'", &["*", "F", "T'"])
+            .rule("T'", &[])
+            .rule("F", &["(", "E", ")"])
+            .rule("F", &["id"])
+            .into_grammar("E")
+            .unwrap();
+        let first = g.first_sets();
+        assert_eq!(first["E"], set(&["(", "id"]));
+        assert_eq!(first["E'"], set(&["+"]));
+        assert_eq!(first["T'"], set(&["*"]));
+        let follow = g.follow_sets();
+        assert_eq!(follow["E"], set(&[")", Grammar::EOF]));
+        assert_eq!(follow["E'"], set(&[")", Grammar::EOF]));
+        assert_eq!(follow["T"], set(&["+", ")", Grammar::EOF]));
+        assert_eq!(follow["T'"], set(&["+", ")", Grammar::EOF]));
+        assert_eq!(follow["F"], set(&["*", "+", ")", Grammar::EOF]));
+    }
+}
@@ -0,0 +1,88 @@
+#![deny(warnings)]
+
+use super::spans::{Span, SpanSource};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::rc::Rc;
+
+// Quote `text` as a DOT string
+pub(crate) fn dot_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Dotted rule of the Span and the input range it covers
+fn span_label(span: &Span) -> String {
+    let mut symbols: Vec<_> = span.rule.spec.iter().map(|s| s.name()).collect();
+    symbols.insert(span.dot, "\u{2022}");
+    let rule = format!("{} -> {}", span.rule.head, symbols.join(" "));
+    format!("{} ({}, {})", rule, span.start, span.end)
+}
+
+/// Render `spans` and the Spans their backpointers lead to as Graphviz DOT.
+/// Spans are grouped in a cluster per StateSet (where they end). Solid
+/// edges go to the Span that got extended, dashed ones to the Span that
+/// completed it. Scans are labelled with the lexeme.
+// Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+#[allow(clippy::mutable_key_type)]
+pub(crate) fn spans_to_dot<'r>(spans: impl Iterator<Item = &'r Rc<Span>>) -> String {
+    // Spans rebuilt out of Leo items aren't shared so they're told by value
+    let mut sources: HashMap<Rc<Span>, Vec<SpanSource>> = HashMap::new();
+    let mut pending: Vec<Rc<Span>> = spans.cloned().collect();
+    while let Some(span) = pending.pop() {
+        if sources.contains_key(&span) {
+            continue;
+        }
+        let span_sources = span.sources();
+        for source in &span_sources {
+            match source {
+                SpanSource::Completion(source, trigger) => {
+                    pending.push(source.clone());
+                    pending.push(trigger.clone());
+                }
+                SpanSource::Scan(source, _, _) => pending.push(source.clone()),
+            }
+        }
+        sources.insert(span, span_sources);
+    }
+    // Number Spans by position so the output doesn't depend on hashing
+    let mut spans: Vec<_> = sources.keys().cloned().collect();
+    spans.sort_by_cached_key(|s| (s.end, s.start, s.rule.to_string(), s.dot));
+    let ids: HashMap<_, _> = spans.iter().enumerate().map(|(id, s)| (s, id)).collect();
+
+    let mut out = String::from("digraph chart {\n  rankdir=RL;\n  node [shape=box];\n");
+    for (id, span) in spans.iter().enumerate() {
+        if id == 0 || spans[id - 1].end != span.end {
+            if id > 0 {
+                out += "  }\n";
+            }
+            writeln!(out, "  subgraph cluster_{} {{", span.end).unwrap();
+            writeln!(out, "    label=\"StateSet {}\";", span.end).unwrap();
+        }
+        let shape = if span.complete() {
+            ", peripheries=2"
+        } else {
+            ""
+        };
+        let label = dot_string(&span_label(span));
+        writeln!(out, "    s{} [label={}{}];", id, label, shape).unwrap();
+    }
+    if !spans.is_empty() {
+        out += "  }\n";
+    }
+    for (id, span) in spans.iter().enumerate() {
+        for source in &sources[span] {
+            match source {
+                SpanSource::Completion(source, trigger) => {
+                    writeln!(out, "  s{} -> s{};", id, ids[source]).unwrap();
+                    writeln!(out, "  s{} -> s{} [style=dashed];", id, ids[trigger]).unwrap();
+                }
+                SpanSource::Scan(source, lexeme, _) => {
+                    let label = dot_string(&format!("'{}'", lexeme));
+                    writeln!(out, "  s{} -> s{} [label={}];", id, ids[source], label).unwrap();
+                }
+            }
+        }
+    }
+    out += "}\n";
+    out
+}
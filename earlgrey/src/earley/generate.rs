@@ -0,0 +1,323 @@
+#![deny(warnings)]
+
+use super::grammar::{Grammar, Rule, Symbol, SymbolId};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Generates sentences (token sequences) out of a `Grammar`, eg: to fuzz a
+/// parser and its semantic actions. Derivations are bounded by `depth`, the
+/// number of rules nested in the tallest branch of their tree.
+/// Terminals get lexemes from the samples or sampler registered for them.
+/// Terminals that match their own name (eg: EBNF `'+'`) don't need any.
+pub struct SentenceGenerator<'g> {
+    grammar: &'g Grammar,
+    samples: HashMap<String, Vec<String>>,
+    samplers: HashMap<String, Box<dyn Fn(u64) -> String + 'g>>,
+    // Height of the shortest derivation tree of each rule, by rule id.
+    // None for rules that can't derive a sentence.
+    heights: Vec<Option<usize>>,
+    // xorshift64* state, random generation is reproducible given a seed
+    state: Cell<u64>,
+}
+
+// Sentences derived by symbol and depth when enumerating them all
+type Derived = HashMap<(SymbolId, usize), Rc<Vec<Vec<String>>>>;
+
+impl<'g> SentenceGenerator<'g> {
+    pub fn new(grammar: &'g Grammar) -> Self {
+        SentenceGenerator {
+            grammar,
+            samples: HashMap::new(),
+            samplers: HashMap::new(),
+            heights: Self::rule_heights(grammar),
+            state: Cell::new(0x2545_f491_4f6c_dd1d),
+        }
+    }
+
+    // A rule's tree is one taller than the tallest of its symbols. Keep
+    // lowering heights until no shorter derivation is found.
+    fn rule_heights(grammar: &Grammar) -> Vec<Option<usize>> {
+        let mut symbols: HashMap<SymbolId, usize> = HashMap::new();
+        let mut rules = vec![None; grammar.rules().len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in grammar.rules() {
+                let height = (rule.spec.iter().zip(&rule.spec_ids))
+                    .map(|(symbol, id)| match &**symbol {
+                        Symbol::Term(_, _) => Some(0),
+                        Symbol::NonTerm(_) => symbols.get(id).copied(),
+                    })
+                    .try_fold(0, |tallest, height| Some(tallest.max(height?)))
+                    .map(|tallest| tallest + 1);
+                let Some(height) = height else {
+                    continue;
+                };
+                if rules[rule.id as usize].is_none_or(|h| height < h) {
+                    rules[rule.id as usize] = Some(height);
+                    changed = true;
+                }
+                let shortest = symbols.entry(rule.head_id).or_insert(height);
+                *shortest = height.min(*shortest);
+            }
+        }
+        rules
+    }
+
+    /// Seed random generation, the same seed yields the same sentences
+    pub fn seed(self, seed: u64) -> Self {
+        // xorshift gets stuck on 0
+        self.state.set(seed.max(1));
+        self
+    }
+
+    /// Lexemes to pick from for `terminal`
+    pub fn samples(mut self, terminal: &str, lexemes: &[&str]) -> Self {
+        let lexemes = lexemes.iter().map(|l| l.to_string()).collect();
+        self.samples.insert(terminal.to_string(), lexemes);
+        self
+    }
+
+    /// Generate lexemes for `terminal` out of a random number. When
+    /// enumerating sentences it contributes a single lexeme, `sampler(0)`.
+    pub fn sampler(mut self, terminal: &str, sampler: impl Fn(u64) -> String + 'g) -> Self {
+        self.samplers
+            .insert(terminal.to_string(), Box::new(sampler));
+        self
+    }
+
+    fn next_random(&self) -> u64 {
+        let mut x = self.state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state.set(x);
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn pick<'a, T>(&self, choices: &'a [T]) -> &'a T {
+        &choices[(self.next_random() % choices.len() as u64) as usize]
+    }
+
+    // Lexemes `terminal` may take, a random one or all of them
+    fn lexemes(&self, terminal: &Symbol, random: bool) -> Result<Vec<String>, String> {
+        let name = terminal.name();
+        let lexemes = if let Some(sampler) = self.samplers.get(name) {
+            vec![sampler(if random { self.next_random() } else { 0 })]
+        } else if let Some(samples) = self.samples.get(name).filter(|s| !s.is_empty()) {
+            match random {
+                true => vec![self.pick(samples).clone()],
+                false => samples.clone(),
+            }
+        } else if terminal.matches(name) {
+            vec![name.to_string()]
+        } else {
+            return Err(format!("Missing Sample: {}", name));
+        };
+        match lexemes.iter().find(|lexeme| !terminal.matches(lexeme)) {
+            Some(lexeme) => Err(format!("Bad Sample: '{}' for {}", lexeme, name)),
+            None => Ok(lexemes),
+        }
+    }
+
+    // Rules of `symbol` that derive a sentence within `depth`
+    fn rules_within(&self, symbol: SymbolId, depth: usize) -> Vec<&Rule> {
+        (self.grammar.rules_for(symbol).iter())
+            .filter(|rule| self.heights[rule.id as usize].is_some_and(|h| h <= depth))
+            .map(|rule| &**rule)
+            .collect()
+    }
+
+    fn start_id(&self, start: &str) -> Result<SymbolId, String> {
+        self.grammar
+            .symbol_id(start)
+            .filter(|&id| !self.grammar.rules_for(id).is_empty())
+            .ok_or_else(|| format!("Missing start Symbol: {}", start))
+    }
+
+    /// A random sentence of `start` whose derivation fits within `depth`
+    pub fn random(&self, start: &str, depth: usize) -> Result<Vec<String>, String> {
+        let mut sentence = Vec::new();
+        self.random_helper(self.start_id(start)?, start, depth, &mut sentence)?;
+        Ok(sentence)
+    }
+
+    fn random_helper(
+        &self,
+        symbol: SymbolId,
+        name: &str,
+        depth: usize,
+        out: &mut Vec<String>,
+    ) -> Result<(), String> {
+        let rules = self.rules_within(symbol, depth);
+        if rules.is_empty() {
+            return Err(format!("No derivation of {} within depth {}", name, depth));
+        }
+        let rule = self.pick(&rules);
+        for (s, &id) in rule.spec.iter().zip(&rule.spec_ids) {
+            match &**s {
+                Symbol::Term(_, _) => out.extend(self.lexemes(s, true)?),
+                Symbol::NonTerm(name) => self.random_helper(id, name, depth - 1, out)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Every sentence of `start` whose derivation fits within `depth`, in
+    /// no particular order. Their number grows fast with `depth`.
+    pub fn exhaustive(&self, start: &str, depth: usize) -> Result<Vec<Vec<String>>, String> {
+        let mut derived = Derived::new();
+        let sentences = self.exhaustive_helper(self.start_id(start)?, depth, &mut derived)?;
+        Ok(sentences.to_vec())
+    }
+
+    fn exhaustive_helper(
+        &self,
+        symbol: SymbolId,
+        depth: usize,
+        derived: &mut Derived,
+    ) -> Result<Rc<Vec<Vec<String>>>, String> {
+        if let Some(sentences) = derived.get(&(symbol, depth)) {
+            return Ok(sentences.clone());
+        }
+        let mut sentences = Vec::new();
+        let mut seen = HashSet::new();
+        for rule in self.rules_within(symbol, depth) {
+            // Extend the sentences of the rule prefix with each symbol
+            let mut prefixes = vec![Vec::new()];
+            for (s, &id) in rule.spec.iter().zip(&rule.spec_ids) {
+                let suffixes = match &**s {
+                    Symbol::Term(_, _) => Rc::new(
+                        self.lexemes(s, false)?
+                            .into_iter()
+                            .map(|l| vec![l])
+                            .collect(),
+                    ),
+                    Symbol::NonTerm(_) => self.exhaustive_helper(id, depth - 1, derived)?,
+                };
+                prefixes = (prefixes.iter())
+                    .flat_map(|prefix| {
+                        suffixes
+                            .iter()
+                            .map(move |suffix| prefix.iter().chain(suffix).cloned().collect())
+                    })
+                    .collect();
+            }
+            // Ambiguous grammars derive some sentences more than once
+            for sentence in prefixes {
+                if seen.insert(sentence.clone()) {
+                    sentences.push(sentence);
+                }
+            }
+        }
+        let sentences = Rc::new(sentences);
+        derived.insert((symbol, depth), sentences.clone());
+        Ok(sentences)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::grammar::{Grammar, GrammarBuilder};
+    use super::super::parser::EarleyParser;
+    use super::SentenceGenerator;
+
+    fn grammar_sum() -> Grammar {
+        GrammarBuilder::default()
+            .nonterm("S")
+            .nonterm("E")
+            .terminal("n", |n| n.parse::<u32>().is_ok())
+            .terminal("+", |n| n == "+")
+            .terminal("(", |n| n == "(")
+            .terminal(")", |n| n == ")")
+            .rule("S", &["S", "+", "E"])
+            .rule("S", &["E"])
+            .rule("E", &["n"])
+            .rule("E", &["(", "S", ")"])
+            .into_grammar("S")
+            .unwrap()
+    }
+
+    #[test]
+    fn random_sentences_parse() {
+        let grammar = grammar_sum();
+        let generator = SentenceGenerator::new(&grammar)
+            .seed(7)
+            .sampler("n", |r| (r % 1000).to_string());
+        let parser = EarleyParser::new(grammar_sum());
+        let mut lengths = Vec::new();
+        for _ in 0..100 {
+            let sentence = generator.random("S", 6).unwrap();
+            assert!(parser.parse(sentence.iter()).is_ok(), "{:?}", sentence);
+            lengths.push(sentence.len());
+        }
+        // Sentences vary and the shortest is just a number
+        assert!(lengths.contains(&1));
+        assert!(lengths.iter().any(|&len| len > 5));
+        // Same seed, same sentences
+        let seeded = |seed| {
+            let generator = SentenceGenerator::new(&grammar)
+                .seed(seed)
+                .samples("n", &["1", "2"]);
+            (0..10)
+                .map(|_| generator.random("S", 5).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(seeded(3), seeded(3));
+        assert_ne!(seeded(3), seeded(4));
+    }
+
+    #[test]
+    fn exhaustive_sentences() {
+        let grammar = grammar_sum();
+        let generator = SentenceGenerator::new(&grammar).samples("n", &["1", "2"]);
+        let sentences = |depth| {
+            let mut sentences: Vec<_> = (generator.exhaustive("S", depth).unwrap())
+                .into_iter()
+                .map(|s| s.join(" "))
+                .collect();
+            sentences.sort();
+            sentences
+        };
+        assert!(sentences(1).is_empty());
+        assert_eq!(sentences(2), vec!["1", "2"]);
+        assert_eq!(
+            sentences(3),
+            vec!["1", "1 + 1", "1 + 2", "2", "2 + 1", "2 + 2"]
+        );
+        // All of them parse and none is repeated
+        let parser = EarleyParser::new(grammar_sum());
+        let all = generator.exhaustive("S", 5).unwrap();
+        for sentence in &all {
+            assert!(parser.parse(sentence.iter()).is_ok(), "{:?}", sentence);
+        }
+        let mut unique = all.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), all.len());
+    }
+
+    #[test]
+    fn generator_errors() {
+        let grammar = grammar_sum();
+        let generator = SentenceGenerator::new(&grammar);
+        assert_eq!(
+            generator.random("S", 4),
+            Err("Missing Sample: n".to_string())
+        );
+        assert_eq!(
+            generator.random("X", 4),
+            Err("Missing start Symbol: X".to_string())
+        );
+        let generator = generator.samples("n", &["x"]);
+        assert_eq!(
+            generator.exhaustive("S", 4),
+            Err("Bad Sample: 'x' for n".to_string())
+        );
+        assert_eq!(
+            generator.random("S", 1),
+            Err("No derivation of S within depth 1".to_string())
+        );
+    }
+}
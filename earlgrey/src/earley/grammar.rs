@@ -1,16 +1,38 @@
 #![deny(warnings)]
 
+use super::pattern::Pattern;
+use super::shared::{Matcher, MaybeSendSync, Shared};
+use std::any::Any;
 use std::collections::HashMap;
-use std::rc::Rc;
 use std::{fmt, hash};
 
+// Symbol names are interned by the GrammarBuilder, the parser looks up
+// rules and matches symbols by these ids instead of comparing names.
+pub(crate) type SymbolId = u32;
+
 pub enum Symbol {
     NonTerm(String),
-    // A terminal has a predicate to validate that input is accepted
-    Term(String, Box<dyn Fn(&str) -> bool>),
+    // A terminal has a matcher to validate that input is accepted, it
+    // captures a value out of the input (`()` for plain predicates)
+    Term(String, Box<Matcher>),
 }
 
 impl Symbol {
+    /// Terminal that accepts the input `pred` holds for
+    pub fn term(name: &str, pred: impl Fn(&str) -> bool + MaybeSendSync + 'static) -> Symbol {
+        let matcher = move |s: &str| pred(s).then(|| Box::new(()) as Box<dyn Any>);
+        Symbol::Term(name.to_string(), Box::new(matcher))
+    }
+
+    /// Terminal that accepts the input `capture` parses into a value
+    pub fn term_capture<T: 'static>(
+        name: &str,
+        capture: impl Fn(&str) -> Option<T> + MaybeSendSync + 'static,
+    ) -> Symbol {
+        let matcher = move |s: &str| capture(s).map(|value| Box::new(value) as Box<dyn Any>);
+        Symbol::Term(name.to_string(), Box::new(matcher))
+    }
+
     pub fn name(&self) -> &str {
         match self {
             Symbol::NonTerm(name) => name,
@@ -19,9 +41,14 @@ impl Symbol {
     }
 
     pub fn matches(&self, input: &str) -> bool {
+        self.capture(input).is_some()
+    }
+
+    /// Value a terminal captures out of `input` if it's accepted
+    pub fn capture(&self, input: &str) -> Option<Box<dyn Any>> {
         match self {
             Symbol::Term(_, matcher) => matcher(input),
-            _ => false,
+            _ => None,
         }
     }
 
@@ -36,7 +63,7 @@ impl hash::Hash for Symbol {
         match self {
             Symbol::Term(name, matcher) => {
                 name.hash(state);
-                (matcher as *const dyn Fn(&str) -> bool).hash(state);
+                (matcher as *const Box<Matcher>).hash(state);
             }
             Symbol::NonTerm(name) => name.hash(state),
         }
@@ -48,10 +75,7 @@ impl PartialEq for Symbol {
         match (self, other) {
             (Symbol::Term(s, m1), Symbol::Term(o, m2)) => {
                 s == o
-                    && std::ptr::addr_eq(
-                        &m1 as *const dyn Fn(&str) -> bool,
-                        &m2 as *const dyn Fn(&str) -> bool,
-                    )
+                    && std::ptr::addr_eq(&m1 as *const &Box<Matcher>, &m2 as *const &Box<Matcher>)
             }
             (Symbol::NonTerm(s), Symbol::NonTerm(o)) => s == o,
             _ => false,
@@ -70,34 +94,92 @@ impl fmt::Debug for Symbol {
     }
 }
 
-#[derive(PartialEq, Hash)]
+/// How rules of the same precedence level nest into each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Assoc {
+    Left,
+    Right,
+    NonAssoc,
+}
+
 pub struct Rule {
     pub head: String,
-    pub spec: Vec<Rc<Symbol>>,
+    pub spec: Vec<Shared<Symbol>>,
+    // Precedence level (higher binds tighter) and associativity
+    pub precedence: Option<(u32, Assoc)>,
+    // Trees score the product of their rules' weights (1 by default)
+    pub weight: f64,
+    // Index of the rule in its Grammar and interned ids of its symbols
+    pub(crate) id: u32,
+    pub(crate) head_id: SymbolId,
+    pub(crate) spec_ids: Vec<SymbolId>,
 }
 
 impl Rule {
+    // Rules out of a Grammar get an id of their own so Spans of different
+    // rules never compare equal. Their symbols aren't interned.
     #[cfg(test)]
-    pub fn new(head: &str, spec: &[Rc<Symbol>]) -> Self {
+    pub fn new(head: &str, spec: &[Shared<Symbol>]) -> Self {
+        use std::sync::atomic::{AtomicU32, Ordering};
+        static NEXT_ID: AtomicU32 = AtomicU32::new(0);
         Rule {
             head: head.to_string(),
-            spec: spec.iter().cloned().collect(),
+            spec: spec.to_vec(),
+            precedence: None,
+            weight: 1.0,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            head_id: 0,
+            spec_ids: vec![0; spec.len()],
+        }
+    }
+
+    /// Disambiguation filter. Check if `child` rule can't derive the symbol
+    /// at `position` of this rule. Only applies to the leftmost and rightmost
+    /// symbols of rules that both have a precedence. Lower precedence rules
+    /// can't nest. Same precedence rules only nest as associativity allows.
+    pub fn rejects(&self, position: usize, child: &Rule) -> bool {
+        let (Some((level, assoc)), Some((child_level, _))) = (self.precedence, child.precedence)
+        else {
+            return false;
+        };
+        let leftmost = position == 0;
+        let rightmost = position + 1 == self.spec.len();
+        if !leftmost && !rightmost {
+            return false;
         }
+        child_level < level
+            || child_level == level
+                && match assoc {
+                    Assoc::Left => rightmost,
+                    Assoc::Right => leftmost,
+                    Assoc::NonAssoc => true,
+                }
+    }
+}
+
+// Rules are the same regardless of their precedence and weight
+impl PartialEq for Rule {
+    fn eq(&self, other: &Rule) -> bool {
+        self.head == other.head && self.spec == other.spec
     }
 }
 
+impl hash::Hash for Rule {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.head.hash(state);
+        self.spec.hash(state);
+    }
+}
+
+// How rules are named, eg: to register their semantic actions
+pub fn rule_name(head: &str, spec: &[&str]) -> String {
+    format!("{} -> {}", head, spec.join(" "))
+}
+
 impl fmt::Display for Rule {
     fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "{} -> {}",
-            self.head,
-            self.spec
-                .iter()
-                .map(|s| s.name())
-                .collect::<Vec<_>>()
-                .join(" ")
-        )
+        let spec: Vec<_> = self.spec.iter().map(|s| s.name()).collect();
+        write!(f, "{}", rule_name(&self.head, &spec))
     }
 }
 
@@ -110,7 +192,92 @@ impl fmt::Debug for Rule {
 #[derive(Clone)]
 pub struct Grammar {
     pub start: String,
-    pub rules: Vec<Rc<Rule>>,
+    // Rules are indexed by head when the Grammar is built, a rule's id is
+    // its position
+    rules: Vec<Shared<Rule>>,
+    // Interned symbol names
+    symbol_ids: HashMap<String, SymbolId>,
+    // Rules of each non-terminal, indexed by SymbolId
+    rules_by_head: Vec<Vec<Shared<Rule>>>,
+    // Symbols that can derive the empty string, indexed by SymbolId
+    nullable: Vec<bool>,
+    // Terminals defined by a Pattern, used to scan raw text
+    patterns: HashMap<String, Shared<Pattern>>,
+    // Imported terminals that took the place of a different one by name
+    pub(crate) collisions: Vec<String>,
+}
+
+impl Grammar {
+    fn new(
+        start: String,
+        rules: Vec<Shared<Rule>>,
+        symbol_ids: HashMap<String, SymbolId>,
+        patterns: HashMap<String, Shared<Pattern>>,
+        collisions: Vec<String>,
+    ) -> Grammar {
+        let mut rules_by_head = vec![Vec::new(); symbol_ids.len()];
+        for rule in &rules {
+            rules_by_head[rule.head_id as usize].push(rule.clone());
+        }
+        let nullable = Grammar::nullable_symbols(&rules, symbol_ids.len());
+        Grammar {
+            start,
+            rules,
+            symbol_ids,
+            rules_by_head,
+            nullable,
+            patterns,
+            collisions,
+        }
+    }
+
+    // A rule head is nullable if all symbols in its body are nullable.
+    // Keep marking heads until no new nullable symbols are found.
+    fn nullable_symbols(rules: &[Shared<Rule>], symbols: usize) -> Vec<bool> {
+        let mut nullable = vec![false; symbols];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in rules {
+                let head = rule.head_id as usize;
+                if !nullable[head] && rule.spec_ids.iter().all(|&s| nullable[s as usize]) {
+                    nullable[head] = true;
+                    changed = true;
+                }
+            }
+        }
+        nullable
+    }
+
+    /// Rules of the grammar in the order they were added
+    pub fn rules(&self) -> &[Shared<Rule>] {
+        &self.rules
+    }
+
+    /// Check if a non-terminal can derive the empty string
+    pub fn is_nullable(&self, symbol: &str) -> bool {
+        self.symbol_id(symbol)
+            .is_some_and(|id| self.nullable[id as usize])
+    }
+
+    // Interned id of a symbol name
+    pub(crate) fn symbol_id(&self, name: &str) -> Option<SymbolId> {
+        self.symbol_ids.get(name).copied()
+    }
+
+    // Rules whose head is `symbol`, none for terminals
+    pub(crate) fn rules_for(&self, symbol: SymbolId) -> &[Shared<Rule>] {
+        &self.rules_by_head[symbol as usize]
+    }
+
+    pub(crate) fn is_nullable_id(&self, symbol: SymbolId) -> bool {
+        self.nullable[symbol as usize]
+    }
+
+    /// Pattern of a terminal added with `GrammarBuilder::pattern`
+    pub fn pattern(&self, terminal: &str) -> Option<&Pattern> {
+        self.patterns.get(terminal).map(|p| p.as_ref())
+    }
 }
 
 impl fmt::Debug for Grammar {
@@ -140,18 +307,34 @@ impl fmt::Debug for Grammar {
 
 #[derive(Default)]
 pub struct GrammarBuilder {
-    symbols: HashMap<String, Rc<Symbol>>,
-    rules: Vec<Rc<Rule>>,
+    // Symbols in order of creation, their index is their SymbolId
+    symbols: Vec<Shared<Symbol>>,
+    symbol_ids: HashMap<String, SymbolId>,
+    rules: Vec<Shared<Rule>>,
+    patterns: HashMap<String, Shared<Pattern>>,
+    // Terminal names `import` shared between different terminals
+    collisions: Vec<String>,
     error: Option<String>,
 }
 
 /// Builds a Gramar while validating existence of Symbols and checking rules.
 impl GrammarBuilder {
+    fn symbol(&self, name: &str) -> Option<&Shared<Symbol>> {
+        let id = *self.symbol_ids.get(name)?;
+        Some(&self.symbols[id as usize])
+    }
+
+    // Intern a new symbol
+    fn insert_symbol(&mut self, symbol: Shared<Symbol>) {
+        let id = self.symbols.len() as SymbolId;
+        self.symbol_ids.insert(symbol.name().to_string(), id);
+        self.symbols.push(symbol);
+    }
+
     fn add_symbol(&mut self, symbol: Symbol, ignore_dups: bool) {
         // Check for duplicate symbols to avoid overwriting by mistake
-        if !self.symbols.contains_key(symbol.name()) {
-            self.symbols
-                .insert(symbol.name().to_string(), Rc::new(symbol));
+        if !self.symbol_ids.contains_key(symbol.name()) {
+            self.insert_symbol(Shared::new(symbol));
         } else if !ignore_dups {
             // Convenience for adding symbols programatically
             self.error = Some(format!("Duplicate Symbol: {}", symbol.name()));
@@ -163,8 +346,12 @@ impl GrammarBuilder {
         self
     }
 
-    pub fn terminal(mut self, name: &str, pred: impl Fn(&str) -> bool + 'static) -> Self {
-        self.add_symbol(Symbol::Term(name.into(), Box::new(pred)), false);
+    pub fn terminal(
+        mut self,
+        name: &str,
+        pred: impl Fn(&str) -> bool + MaybeSendSync + 'static,
+    ) -> Self {
+        self.add_symbol(Symbol::term(name, pred), false);
         self
     }
 
@@ -172,19 +359,80 @@ impl GrammarBuilder {
         self.add_symbol(Symbol::NonTerm(name.into()), true);
     }
 
-    pub fn terminal_try(&mut self, name: &str, pred: impl Fn(&str) -> bool + 'static) {
-        self.add_symbol(Symbol::Term(name.into(), Box::new(pred)), true);
+    pub fn terminal_try(
+        &mut self,
+        name: &str,
+        pred: impl Fn(&str) -> bool + MaybeSendSync + 'static,
+    ) {
+        self.add_symbol(Symbol::term(name, pred), true);
+    }
+
+    /// Add a terminal that parses its lexemes into a value of type `T`. It
+    /// accepts the lexemes `capture` returns a value for. The value is handed
+    /// to the terminal parser of an `EarleyForest::new_with_capture` so the
+    /// lexeme doesn't need parsing twice.
+    pub fn terminal_capture<T: 'static>(
+        mut self,
+        name: &str,
+        capture: impl Fn(&str) -> Option<T> + MaybeSendSync + 'static,
+    ) -> Self {
+        self.add_symbol(Symbol::term_capture(name, capture), false);
+        self
+    }
+
+    pub fn terminal_capture_try<T: 'static>(
+        &mut self,
+        name: &str,
+        capture: impl Fn(&str) -> Option<T> + MaybeSendSync + 'static,
+    ) {
+        self.add_symbol(Symbol::term_capture(name, capture), true);
+    }
+
+    fn add_pattern(&mut self, name: &str, pattern: &str, ignore_dups: bool) {
+        let pattern = match Pattern::new(pattern) {
+            Ok(pattern) => Shared::new(pattern),
+            Err(e) => {
+                self.error = Some(e);
+                return;
+            }
+        };
+        if !self.symbol_ids.contains_key(name) {
+            self.patterns.insert(name.to_string(), pattern.clone());
+        }
+        self.add_symbol(
+            Symbol::term(name, move |s| pattern.is_match(s)),
+            ignore_dups,
+        );
+    }
+
+    /// Add a terminal that matches the regex-like `pattern`, eg: `[0-9]+`.
+    /// Unlike plain terminals it can find its own lexemes in raw text which
+    /// makes it efficient for `EarleyParser::parse_str`.
+    pub fn pattern(mut self, name: &str, pattern: &str) -> Self {
+        self.add_pattern(name, pattern, false);
+        self
+    }
+
+    pub fn pattern_try(&mut self, name: &str, pattern: &str) {
+        self.add_pattern(name, pattern, true);
     }
 
     // Register new rules for the grammar
-    fn add_rule(&mut self, head: &str, spec: &[&str], ignore_dups: bool) {
+    pub(crate) fn add_rule(
+        &mut self,
+        head: &str,
+        spec: &[&str],
+        precedence: Option<(u32, Assoc)>,
+        weight: f64,
+        ignore_dups: bool,
+    ) {
         // First check that all symbols have been registered (need references)
-        if let Some(s) = spec.iter().find(|&n| !self.symbols.contains_key(*n)) {
+        if let Some(s) = spec.iter().find(|&n| !self.symbol_ids.contains_key(*n)) {
             self.error = Some(format!("Missing Symbol: {}", s));
             return;
         }
         // Check the head
-        if let Some(s) = self.symbols.get(head) {
+        if let Some(s) = self.symbol(head) {
             if s.is_terminal() {
                 self.error = Some(format!("Rule head must be Term: {}", head));
                 return;
@@ -193,12 +441,28 @@ impl GrammarBuilder {
             self.error = Some(format!("Missing Symbol: {}", head));
             return;
         }
+        if !weight.is_finite() || weight < 0.0 {
+            self.error = Some(format!(
+                "Bad Weight: {} for {}",
+                weight,
+                rule_name(head, spec)
+            ));
+            return;
+        }
         // Build the rule
-        let rule = Rc::new(Rule {
+        let rule = Shared::new(Rule {
             head: head.to_string(),
-            spec: spec.iter().map(|&s| self.symbols[s].clone()).collect(),
+            spec: spec
+                .iter()
+                .map(|&s| self.symbol(s).unwrap().clone())
+                .collect(),
+            precedence,
+            weight,
+            id: self.rules.len() as u32,
+            head_id: self.symbol_ids[head],
+            spec_ids: spec.iter().map(|&s| self.symbol_ids[s]).collect(),
         });
-        // Check this rule is only added once. NOTE: `Rc`s equal on inner value
+        // Check this rule is only added once. NOTE: `Shared`s equal on inner value
         if !self.rules.contains(&rule) {
             self.rules.push(rule);
         } else if !ignore_dups {
@@ -207,17 +471,106 @@ impl GrammarBuilder {
     }
 
     pub fn rule(mut self, head: &str, spec: &[&str]) -> Self {
-        self.add_rule(head, spec, false);
+        self.add_rule(head, spec, None, 1.0, false);
         self
     }
 
     pub fn rule_try(&mut self, head: &str, spec: &[&str]) {
-        self.add_rule(head, spec, true)
+        self.add_rule(head, spec, None, 1.0, true)
+    }
+
+    /// Add a rule with a precedence `level` (higher binds tighter) and
+    /// associativity. Trees where these rules nest otherwise are pruned by
+    /// `EarleyForest` as it builds them.
+    pub fn rule_prec(mut self, head: &str, spec: &[&str], level: u32, assoc: Assoc) -> Self {
+        self.add_rule(head, spec, Some((level, assoc)), 1.0, false);
+        self
+    }
+
+    pub fn rule_prec_try(&mut self, head: &str, spec: &[&str], level: u32, assoc: Assoc) {
+        self.add_rule(head, spec, Some((level, assoc)), 1.0, true)
+    }
+
+    /// Add a rule with a `weight` (eg: a probability) to rank ambiguous
+    /// parses. A tree scores the product of its rules' weights, see
+    /// `EarleyForest::eval_best`. Weights must be non-negative.
+    pub fn rule_weighted(mut self, head: &str, spec: &[&str], weight: f64) -> Self {
+        self.add_rule(head, spec, None, weight, false);
+        self
+    }
+
+    pub fn rule_weighted_try(&mut self, head: &str, spec: &[&str], weight: f64) {
+        self.add_rule(head, spec, None, weight, true)
+    }
+
+    /// Import all rules of `grammar` with its non-terminals renamed to
+    /// `prefix.name` (eg: `time.date`) so they don't clash with ours, rules
+    /// can then refer to them by that name. Terminals keep their name, ones
+    /// we already have are shared. Precedence and weights are kept. Sharing
+    /// a name between terminals that aren't the same is reported by `lint`.
+    pub fn import(mut self, prefix: &str, grammar: &Grammar) -> Self {
+        let rename = |name: &str| format!("{}.{}", prefix, name);
+        for name in &grammar.collisions {
+            self.add_collision(name);
+        }
+        for rule in &grammar.rules {
+            self.nonterm_try(&rename(&rule.head));
+            let mut spec = Vec::new();
+            for symbol in &rule.spec {
+                match &**symbol {
+                    Symbol::NonTerm(name) => {
+                        self.nonterm_try(&rename(name));
+                        spec.push(rename(name));
+                    }
+                    Symbol::Term(name, _) => {
+                        match self.symbol(name) {
+                            Some(s) if !s.is_terminal() => {
+                                self.error = Some(format!("Duplicate Symbol: {}", name));
+                                return self;
+                            }
+                            Some(s) => {
+                                // Patterns (eg: EBNF literals) are the same if their
+                                // source is, other terminals only if it's the same one
+                                let ours = self.patterns.get(name).map(|p| p.as_str());
+                                let theirs = grammar.patterns.get(name).map(|p| p.as_str());
+                                let same =
+                                    Shared::ptr_eq(s, symbol) || ours.is_some() && ours == theirs;
+                                if !same {
+                                    self.add_collision(name);
+                                }
+                            }
+                            None => {
+                                if let Some(pattern) = grammar.patterns.get(name) {
+                                    self.patterns.insert(name.clone(), pattern.clone());
+                                }
+                                self.insert_symbol(symbol.clone());
+                            }
+                        }
+                        spec.push(name.clone());
+                    }
+                }
+            }
+            let spec: Vec<_> = spec.iter().map(String::as_str).collect();
+            self.add_rule(
+                &rename(&rule.head),
+                &spec,
+                rule.precedence,
+                rule.weight,
+                false,
+            );
+        }
+        self
+    }
+
+    fn add_collision(&mut self, name: &str) {
+        if !self.collisions.iter().any(|c| c == name) {
+            self.collisions.push(name.to_string());
+        }
     }
 
     pub fn into_grammar(mut self, start: &str) -> Result<Grammar, String> {
-        let start = start.into();
-        if let Some(s) = self.symbols.get(&start) {
+        let start = start.to_string();
+        if let Some(s) = self.symbol(&start) {
             if s.is_terminal() {
                 self.error = Some(format!("Grammar start must be NonTerm: {}", start));
             }
@@ -225,10 +578,13 @@ impl GrammarBuilder {
             self.error = Some(format!("Missing start Symbol: {}", start));
         }
         self.error.map_or(
-            Ok(Grammar {
+            Ok(Grammar::new(
                 start,
-                rules: self.rules,
-            }),
+                self.rules,
+                self.symbol_ids,
+                self.patterns,
+                self.collisions,
+            )),
             Err,
         )
     }
@@ -243,30 +599,102 @@ impl GrammarBuilder {
 
 #[cfg(test)]
 mod tests {
-    use super::{GrammarBuilder, Symbol};
+    use super::{Assoc, GrammarBuilder, Symbol};
     use std::collections::HashSet;
 
+    #[test]
+    fn rule_precedence_filter() {
+        let g = GrammarBuilder::default()
+            .nonterm("E")
+            .terminal("n", |n| n == "n")
+            .terminal("+", |n| n == "+")
+            .terminal("*", |n| n == "*")
+            .terminal("^", |n| n == "^")
+            .terminal("<", |n| n == "<")
+            .terminal("(", |n| n == "(")
+            .terminal(")", |n| n == ")")
+            .rule_prec("E", &["E", "<", "E"], 0, Assoc::NonAssoc)
+            .rule_prec("E", &["E", "+", "E"], 1, Assoc::Left)
+            .rule_prec("E", &["E", "*", "E"], 2, Assoc::Left)
+            .rule_prec("E", &["E", "^", "E"], 3, Assoc::Right)
+            .rule("E", &["(", "E", ")"])
+            .rule("E", &["n"])
+            .into_grammar("E")
+            .unwrap();
+        let (lt, sum, mul, pow, group) = (
+            &g.rules[0],
+            &g.rules[1],
+            &g.rules[2],
+            &g.rules[3],
+            &g.rules[4],
+        );
+        // Lower precedence can't nest in either operand
+        assert!(mul.rejects(0, sum));
+        assert!(mul.rejects(2, sum));
+        assert!(!sum.rejects(0, mul));
+        assert!(!sum.rejects(2, mul));
+        // Associativity
+        assert!(!sum.rejects(0, sum));
+        assert!(sum.rejects(2, sum));
+        assert!(pow.rejects(0, pow));
+        assert!(!pow.rejects(2, pow));
+        assert!(lt.rejects(0, lt));
+        assert!(lt.rejects(2, lt));
+        // Rules without precedence (or inner symbols) are never filtered
+        assert!(!group.rejects(1, sum));
+        assert!(!mul.rejects(0, group));
+    }
+
+    #[test]
+    fn grammar_nullable() {
+        // A -> B C | a ; B -> <e> | b ; C -> B B ; D -> A a ; E -> E
+        let g = GrammarBuilder::default()
+            .nonterm("A")
+            .nonterm("B")
+            .nonterm("C")
+            .nonterm("D")
+            .nonterm("E")
+            .terminal("a", |n| n == "a")
+            .terminal("b", |n| n == "b")
+            .rule("A", &["B", "C"])
+            .rule("A", &["a"])
+            .rule("B", &[])
+            .rule("B", &["b"])
+            .rule("C", &["B", "B"])
+            .rule("D", &["A", "a"])
+            .rule("E", &["E"])
+            .into_grammar("A")
+            .unwrap();
+        assert!(g.is_nullable("A"));
+        assert!(g.is_nullable("B"));
+        assert!(g.is_nullable("C"));
+        assert!(!g.is_nullable("D"));
+        assert!(!g.is_nullable("E"));
+        assert!(!g.is_nullable("a"));
+    }
+
     #[test]
     fn symbol_check_eq_hash() {
         assert_ne!(
             Symbol::NonTerm("X".to_string()),
-            Symbol::Term("X".to_string(), Box::new(|_| true))
+            Symbol::term("X", |_| true)
         );
         // Check that term and non-term of equal name are not the same
         let mut m = HashSet::new();
         m.insert(Symbol::NonTerm("X".to_string()));
-        m.insert(Symbol::Term("X".to_string(), Box::new(|_| true)));
+        m.insert(Symbol::term("X", |_| true));
         assert_eq!(m.len(), 2);
     }
 
     #[test]
     fn symbol_terminal_matches() {
-        let term = Symbol::Term(
-            "uint".to_string(),
-            Box::new(|n| n.chars().all(|c| "1234567890".contains(c))),
-        );
+        let term = Symbol::term("uint", |n| n.chars().all(|c| "1234567890".contains(c)));
         assert_eq!(term.name(), "uint");
         assert!(term.matches("123"));
+        assert!(term.capture("123").unwrap().is::<()>());
+        let term = Symbol::term_capture("uint", |n| n.parse::<u32>().ok());
+        assert!(!term.matches("x"));
+        assert_eq!(term.capture("123").unwrap().downcast_ref(), Some(&123_u32));
     }
 
     #[test]
@@ -340,4 +768,93 @@ mod tests {
             .into_grammar("Sum");
         assert_eq!(g.unwrap_err(), "Missing Symbol: Rum");
     }
+
+    #[test]
+    fn grammar_bad_weight() {
+        let g = GrammarBuilder::default()
+            .nonterm("Sum")
+            .terminal("Num", |n| n.chars().all(|c| "123".contains(c)))
+            .rule_weighted("Sum", &["Num"], 0.5)
+            .into_grammar("Sum");
+        assert_eq!(g.unwrap().rules[0].weight, 0.5);
+        let g = GrammarBuilder::default()
+            .nonterm("Sum")
+            .terminal("Num", |n| n.chars().all(|c| "123".contains(c)))
+            .rule_weighted("Sum", &["Num", "Num"], -1.0)
+            .into_grammar("Sum");
+        assert_eq!(g.unwrap_err(), "Bad Weight: -1 for Sum -> Num Num");
+    }
+
+    #[test]
+    fn grammar_import() {
+        let sum = GrammarBuilder::default()
+            .nonterm("Sum")
+            .terminal("Num", |n| n.chars().all(|c| "123".contains(c)))
+            .pattern("+", r"\+")
+            .rule_weighted("Sum", &["Sum", "+", "Num"], 0.5)
+            .rule("Sum", &["Num"])
+            .into_grammar("Sum")
+            .unwrap();
+        let g = GrammarBuilder::default()
+            .nonterm("Sum")
+            .nonterm("List")
+            .terminal("+", |n| n == "+")
+            .terminal(",", |n| n == ",")
+            .import("sum", &sum)
+            .rule("List", &["List", ",", "sum.Sum"])
+            .rule("List", &["sum.Sum"])
+            .into_grammar("List")
+            .unwrap();
+        let rules: Vec<_> = g.rules.iter().map(|r| r.to_string()).collect();
+        assert_eq!(
+            rules,
+            vec![
+                "sum.Sum -> sum.Sum + Num",
+                "sum.Sum -> Num",
+                "List -> List , sum.Sum",
+                "List -> sum.Sum",
+            ]
+        );
+        assert_eq!(g.rules[0].weight, 0.5);
+        // Our own `+` terminal was shared, its pattern not imported
+        assert!(g.pattern("+").is_none());
+        assert!(g.rules[0].spec[1].matches("+"));
+        assert_eq!(g.collisions, vec!["+"]);
+
+        let g = GrammarBuilder::default()
+            .nonterm("Num")
+            .import("sum", &sum)
+            .into_grammar("sum.Sum");
+        assert_eq!(g.unwrap_err(), "Duplicate Symbol: Num");
+    }
+
+    #[test]
+    fn grammar_rules_by_head() {
+        let g = GrammarBuilder::default()
+            .nonterm("E")
+            .nonterm("T")
+            .terminal("n", |n| n == "n")
+            .terminal("+", |n| n == "+")
+            .rule("E", &["E", "+", "T"])
+            .rule("T", &["n"])
+            .rule("E", &["T"])
+            .into_grammar("E")
+            .unwrap();
+        assert_eq!(g.symbol_id("x"), None);
+        let rules = |head: &str| -> Vec<_> {
+            let rules = g.rules_for(g.symbol_id(head).unwrap()).iter();
+            rules.map(|r| r.to_string()).collect()
+        };
+        assert_eq!(rules("E"), vec!["E -> E + T", "E -> T"]);
+        assert_eq!(rules("T"), vec!["T -> n"]);
+        assert!(rules("n").is_empty());
+        // Rules know their position in the grammar and their symbol ids
+        for (idx, rule) in g.rules.iter().enumerate() {
+            assert_eq!(rule.id as usize, idx);
+            assert_eq!(Some(rule.head_id), g.symbol_id(&rule.head));
+            let spec: Vec<_> = rule.spec.iter().map(|s| g.symbol_id(s.name())).collect();
+            let spec_ids: Vec<_> = rule.spec_ids.iter().map(|&id| Some(id)).collect();
+            assert_eq!(spec, spec_ids);
+        }
+    }
 }
@@ -1,14 +1,41 @@
 #![deny(warnings)]
 
 mod grammar;
-pub use grammar::{Grammar, GrammarBuilder};
+pub use grammar::{Assoc, Grammar, GrammarBuilder, Rule};
+
+mod analysis;
+pub use analysis::Lint;
+
+mod serialize;
+pub(crate) use serialize::{is_identifier, json_string};
+
+mod dot;
+pub(crate) use dot::dot_string;
+
+mod shared;
+pub use shared::MaybeSendSync;
+
+mod pattern;
+pub use pattern::Pattern;
+
+mod observer;
+pub use observer::{ParseObserver, ParseStats, RuleStats};
 
 mod parser;
 mod spans;
-pub use parser::EarleyParser;
+pub use parser::{EarleyParser, ParseError, ParseSession, PartialParse};
 
 mod trees;
 pub use trees::EarleyForest;
 
+mod semantic;
+pub use semantic::SemanticGrammarBuilder;
+
+mod sppf;
+pub use sppf::{Derivation, PackedNode, Sppf, SppfLabel, SppfNode};
+
+mod generate;
+pub use generate::SentenceGenerator;
+
 #[cfg(test)]
 mod parser_test;
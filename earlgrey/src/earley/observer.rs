@@ -0,0 +1,144 @@
+#![deny(warnings)]
+
+use super::grammar::Rule;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Hooks called by `EarleyParser::parse_observed` as the chart is built,
+/// eg: to trace or profile a parse. Positions are StateSet indexes.
+/// All hooks do nothing by default, `()` is an observer that ignores all.
+pub trait ParseObserver {
+    /// A Span for `rule` was predicted at `position`
+    fn predict(&mut self, _rule: &Rule, _position: usize) {}
+
+    /// A Span of `rule` scanned `lexeme` moving on to `position`
+    fn scan(&mut self, _rule: &Rule, _lexeme: &str, _position: usize) {}
+
+    /// A Span completed `rule` over `start..position`. It's about to advance
+    /// the Spans waiting on the rule head at `start`.
+    fn complete(&mut self, _rule: &Rule, _start: usize, _position: usize) {}
+
+    /// StateSet at `position` is done and holds `size` Spans
+    fn stateset(&mut self, _position: usize, _size: usize) {}
+}
+
+impl ParseObserver for () {}
+
+/// Number of chart events seen for a rule
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RuleStats {
+    pub predictions: usize,
+    pub scans: usize,
+    pub completions: usize,
+}
+
+impl RuleStats {
+    fn total(&self) -> usize {
+        self.predictions + self.scans + self.completions
+    }
+}
+
+/// A `ParseObserver` that counts events per rule and the Spans in each
+/// StateSet, to find out which rules blow up the chart.
+#[derive(Clone, Debug, Default)]
+pub struct ParseStats {
+    /// Events per rule, keyed by the rule name (eg: `E -> E + n`)
+    pub rules: HashMap<String, RuleStats>,
+    /// Number of Spans in each StateSet
+    pub statesets: Vec<usize>,
+}
+
+impl ParseObserver for ParseStats {
+    fn predict(&mut self, rule: &Rule, _: usize) {
+        self.rules.entry(rule.to_string()).or_default().predictions += 1;
+    }
+
+    fn scan(&mut self, rule: &Rule, _: &str, _: usize) {
+        self.rules.entry(rule.to_string()).or_default().scans += 1;
+    }
+
+    fn complete(&mut self, rule: &Rule, _: usize, _: usize) {
+        self.rules.entry(rule.to_string()).or_default().completions += 1;
+    }
+
+    fn stateset(&mut self, position: usize, size: usize) {
+        if self.statesets.len() <= position {
+            self.statesets.resize(position + 1, 0);
+        }
+        self.statesets[position] = size;
+    }
+}
+
+impl ParseStats {
+    /// Total number of Spans in the chart
+    pub fn spans(&self) -> usize {
+        self.statesets.iter().sum()
+    }
+
+    /// Table of events per rule, busiest rules first
+    pub fn report(&self) -> String {
+        let mut rules: Vec<_> = self.rules.iter().collect();
+        rules.sort_by(|(r1, s1), (r2, s2)| s2.total().cmp(&s1.total()).then(r1.cmp(r2)));
+        let largest = (self.statesets.iter().enumerate()).max_by_key(|&(idx, size)| (size, !idx));
+        let mut out = format!(
+            "StateSets: {}, Spans: {}",
+            self.statesets.len(),
+            self.spans()
+        );
+        if let Some((idx, size)) = largest {
+            write!(out, " (largest: {} at {})", size, idx).unwrap();
+        }
+        writeln!(
+            out,
+            "\n{:>8} {:>8} {:>8}  rule",
+            "predict", "scan", "complete"
+        )
+        .unwrap();
+        for (rule, stats) in rules {
+            writeln!(
+                out,
+                "{:>8} {:>8} {:>8}  {}",
+                stats.predictions, stats.scans, stats.completions, rule
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::grammar::GrammarBuilder;
+    use super::super::parser::EarleyParser;
+    use super::{ParseStats, RuleStats};
+
+    #[test]
+    fn parse_stats() {
+        let grammar = GrammarBuilder::default()
+            .nonterm("E")
+            .terminal("n", |n| n.parse::<u32>().is_ok())
+            .terminal("+", |n| n == "+")
+            .rule("E", &["E", "+", "n"])
+            .rule("E", &["n"])
+            .into_grammar("E")
+            .unwrap();
+        let parser = EarleyParser::new(grammar);
+        let mut stats = ParseStats::default();
+        let trees = parser.parse_observed("1 + 2 + 3".split_whitespace(), &mut stats);
+        assert!(trees.is_ok());
+        assert_eq!(stats.statesets, vec![2, 2, 1, 2, 1, 2]);
+        let counts = |predictions, scans, completions| RuleStats {
+            predictions,
+            scans,
+            completions,
+        };
+        assert_eq!(stats.rules["E -> E + n"], counts(1, 4, 2));
+        assert_eq!(stats.rules["E -> n"], counts(1, 1, 1));
+        let expected = r#"StateSets: 6, Spans: 10 (largest: 2 at 0)
+ predict     scan complete  rule
+       1        4        2  E -> E + n
+       1        1        1  E -> n
+"#;
+        assert_eq!(stats.report(), expected);
+    }
+}
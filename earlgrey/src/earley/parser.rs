@@ -1,9 +1,12 @@
 #![deny(warnings)]
 
-use super::grammar::{Grammar, Symbol};
-use super::spans::{Span, SpanSource};
-use std::collections::HashSet;
-use std::fmt::Debug;
+use super::dot::spans_to_dot;
+use super::grammar::{Grammar, Symbol, SymbolId};
+use super::observer::ParseObserver;
+use super::spans::{Capture, LeoItem, Span, SpanSource};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Debug};
 use std::rc::Rc;
 
 pub struct EarleyParser {
@@ -13,6 +16,67 @@ pub struct EarleyParser {
 #[derive(Debug)]
 pub struct ParseTrees(pub Vec<Rc<Span>>);
 
+impl ParseTrees {
+    /// Graphviz DOT of the Spans reachable from the roots through their
+    /// backpointers, ie: every Span that takes part in some parse tree.
+    pub fn to_dot(&self) -> String {
+        spans_to_dot(self.0.iter())
+    }
+}
+
+/// Complete parses of a symbol covering only tokens `start..end` of the
+/// input. Span positions in `trees` are indexes in the whole input.
+#[derive(Debug)]
+pub struct PartialParse {
+    pub start: usize,
+    pub end: usize,
+    pub trees: ParseTrees,
+}
+
+/// Reports where the input stopped being acceptable by the grammar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// Token index where parsing failed (ie: the StateSet went empty).
+    /// Byte offset into the input when parsing with `parse_str`.
+    pub position: usize,
+    /// If `position` is a byte offset (`parse_str`) rather than a token index
+    pub in_bytes: bool,
+    /// Offending lexeme, `None` if the input ended before a rule completed
+    pub lexeme: Option<String>,
+    /// Terminals the items in the last non-empty StateSet were waiting for
+    pub expected: Vec<String>,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.lexeme {
+            Some(lexeme) => write!(f, "Parse Error: unexpected `{}`", lexeme)?,
+            None => write!(f, "Parse Error: unexpected end of input")?,
+        }
+        let unit = if self.in_bytes { "byte" } else { "token" };
+        write!(f, " at {} {}", unit, self.position)?;
+        if !self.expected.is_empty() {
+            let expected = self
+                .expected
+                .iter()
+                .map(|t| format!("`{}`", t))
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, ", expected one of {}", expected)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// Allow callers that bubble up String errors to keep using `?`
+impl From<ParseError> for String {
+    fn from(error: ParseError) -> String {
+        error.to_string()
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 impl EarleyParser {
@@ -34,10 +98,7 @@ impl EarleyParser {
         );
         Box::new(
             starting_stateset
-                .filter(move |span| match span.next_symbol() {
-                    Some(Symbol::NonTerm(name)) => name == &trigger.rule.head,
-                    _ => false,
-                })
+                .filter(move |span| span.next_symbol_id() == Some(trigger.rule.head_id))
                 .map(move |span| {
                     Span::extend(
                         SpanSource::Completion(span.clone(), trigger.clone()),
@@ -56,87 +117,365 @@ impl EarleyParser {
     ) -> impl Iterator<Item = Rc<Span>> + 'r {
         current_stateset
             // check span's next symbol is a temrinal that scans lexeme
-            .filter(move |span| span.next_symbol().is_some_and(|s| s.matches(lexeme)))
-            .map(move |span| {
-                Rc::new(Span::extend(
-                    SpanSource::Scan(span.clone(), lexeme.to_string()),
+            .filter_map(move |span| {
+                let capture = span.next_symbol()?.capture(lexeme)?;
+                Some(Rc::new(Span::extend(
+                    SpanSource::Scan(span.clone(), lexeme.to_string(), Capture(capture.into())),
                     end,
-                ))
+                )))
+            })
+    }
+
+    /// Build new items for the rules of `symbol` that `trigger` is waiting on.
+    /// If `symbol` is nullable also advance `trigger` over it (Aycock-Horspool)
+    /// with the empty completions found so far. Empty completions found
+    /// later are merged in when they complete `trigger` the regular way.
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashSet is sound
+    #[allow(clippy::mutable_key_type)]
+    fn predictions(
+        &self,
+        stateset: &HashSet<Rc<Span>>,
+        trigger: &Rc<Span>,
+        symbol: SymbolId,
+    ) -> Vec<Span> {
+        let mut new_items: Vec<_> = (self.grammar.rules_for(symbol).iter())
+            .map(|rule| Span::new(rule, trigger.end))
+            .collect();
+        if self.grammar.is_nullable_id(symbol) {
+            new_items.push(Span::skip_nullable(trigger));
+            new_items.extend(
+                stateset
+                    .iter()
+                    .filter(|span| {
+                        span.complete() && span.start == trigger.end && span.rule.head_id == symbol
+                    })
+                    .map(|empty| {
+                        Span::extend(
+                            SpanSource::Completion(trigger.clone(), empty.clone()),
+                            trigger.end,
+                        )
+                    }),
+            );
+        }
+        new_items
+    }
+
+    /// Leo item for `symbol` at the StateSet `origin`, memoized in `memo`.
+    /// There's one if a single Span in the StateSet waits on `symbol` and
+    /// it's the last symbol of its rule. Only valid for complete StateSets.
+    /// `root` is the symbol parsed and the StateSet where its parse starts,
+    /// `None` if it's parsed from every StateSet.
+    fn leo_item(
+        statesets: &[HashSet<Rc<Span>>],
+        memo: &mut HashMap<(usize, SymbolId), Option<Rc<LeoItem>>>,
+        root: (Option<SymbolId>, Option<usize>),
+        origin: usize,
+        symbol: SymbolId,
+    ) -> Option<Rc<LeoItem>> {
+        let key = (origin, symbol);
+        if let Some(leo) = memo.get(&key) {
+            return leo.clone();
+        }
+        // Spans completing the root symbol need to make it to the chart
+        if Some(symbol) == root.0 && root.1.is_none_or(|start| start == origin) {
+            return None;
+        }
+        // Mark as visited to stop on cycles of unit rules (eg: A -> B, B -> A)
+        memo.insert(key, None);
+        let mut waiting =
+            (statesets[origin].iter()).filter(|span| span.next_symbol_id() == Some(symbol));
+        let leo = match (waiting.next(), waiting.next()) {
+            (Some(source), None) if source.dot + 1 == source.rule.spec.len() => {
+                let next = Self::leo_item(statesets, memo, root, source.start, source.rule.head_id);
+                Some(Rc::new(LeoItem::new(source.clone(), next)))
+            }
+            _ => None,
+        };
+        memo.insert(key, leo.clone());
+        leo
+    }
+
+    /// Build a `ParseError` from the last non-empty `stateset` collecting the
+    /// names of the terminals its items were expecting to scan.
+    fn parse_error<'r>(
+        stateset: impl Iterator<Item = &'r Rc<Span>>,
+        position: usize,
+        lexeme: Option<String>,
+    ) -> ParseError {
+        let mut expected: Vec<_> = stateset
+            .filter_map(|span| match span.next_symbol() {
+                Some(Symbol::Term(name, _)) => Some(name.clone()),
+                _ => None,
             })
+            .collect();
+        expected.sort();
+        expected.dedup();
+        ParseError {
+            position,
+            in_bytes: false,
+            lexeme,
+            expected,
+        }
+    }
+
+    /// Start a `ParseSession` to feed input one token at a time
+    pub fn session(&self) -> ParseSession<'_> {
+        self.session_for(&self.grammar.start)
+    }
+
+    /// Start a `ParseSession` for `symbol` instead of the start symbol
+    pub fn session_for(&self, symbol: &str) -> ParseSession<'_> {
+        ParseSession::starting_at(self, symbol, Some(0), &mut ())
     }
 
-    pub fn parse<T>(&self, mut tokenizer: T) -> Result<ParseTrees, String>
+    pub fn parse<T>(&self, tokenizer: T) -> Result<ParseTrees, ParseError>
     where
         T: Iterator,
         T::Item: Debug + AsRef<str>,
     {
-        // Populate S0, add items for each rule matching the start symbol
-        let s0: HashSet<_> = self
-            .grammar
-            .rules
-            .iter()
-            .filter(|rule| rule.head == self.grammar.start)
-            .map(|rule| Rc::new(Span::new(rule, 0)))
-            .collect();
+        self.parse_symbol(&self.grammar.start, tokenizer)
+    }
+
+    /// Parse the input as `symbol`, any non-terminal of the grammar, as if
+    /// it were the start symbol. Saves building a grammar per entry point.
+    pub fn parse_symbol<T>(&self, symbol: &str, tokenizer: T) -> Result<ParseTrees, ParseError>
+    where
+        T: Iterator,
+        T::Item: Debug + AsRef<str>,
+    {
+        let mut session = self.session_for(symbol);
+        for lexeme in tokenizer {
+            session.feed(lexeme.as_ref())?;
+        }
+        Self::finish(&session)
+    }
 
-        let mut statesets = vec![s0];
+    /// Parse like `parse` reporting every step of building the chart to
+    /// `observer`, eg: a `ParseStats` to find which rules blow it up.
+    pub fn parse_observed<T>(
+        &self,
+        tokenizer: T,
+        observer: &mut dyn ParseObserver,
+    ) -> Result<ParseTrees, ParseError>
+    where
+        T: Iterator,
+        T::Item: Debug + AsRef<str>,
+    {
+        let root = &self.grammar.start;
+        let mut session = ParseSession::starting_at(self, root, Some(0), observer);
+        for lexeme in tokenizer {
+            session.feed_observed(lexeme.as_ref(), observer)?;
+        }
+        Self::finish(&session)
+    }
 
-        // New statesets are generated from input stream (Scans)
-        for idx in 0.. {
-            // Predict/Complete until no new Spans are added to the StateSet
-            // Instead of looping we could pre-populate completions of nullable symbols
-            loop {
-                let new_items: Vec<_> = statesets[idx]
-                    .iter()
-                    .flat_map(|trigger| {
-                        let next_sym = trigger.next_symbol();
-                        if let Some(Symbol::NonTerm(next_terminal)) = next_sym {
-                            // Prediction: Build new items from `next_terminal` of some Symbol
-                            Box::new(
-                                self.grammar
-                                    .rules
-                                    .iter()
-                                    .filter(|rule| rule.head == *next_terminal)
-                                    .map(move |rule| Span::new(rule, idx)),
-                            )
-                        } else if trigger.complete() {
-                            assert!(next_sym.is_none(), "Expected next symbol to be None");
-                            EarleyParser::completions(statesets[trigger.start].iter(), trigger, idx)
-                        } else {
-                            // Scan items populate next stateset only when done with current state
-                            assert!(matches!(next_sym, Some(&Symbol::Term(_, _))));
-                            Box::new(std::iter::empty())
-                        }
-                    })
-                    .collect();
-                let stateset = statesets.get_mut(idx).unwrap();
-                let prev_len = stateset.len();
-                // Add new items to the current stateset merging existing ones
-                for new_item in new_items {
+    /// Build `Scan` items for items in `stateset` waiting on a terminal that
+    /// matches a prefix of `input` from `offset`. Terminals with a `Pattern`
+    /// find all their lexemes at once, plain terminals try every prefix.
+    /// Pattern terminals capture `()`, plain ones whatever their matcher does.
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashSet is sound
+    #[allow(clippy::mutable_key_type)]
+    fn scans_str(&self, stateset: &HashSet<Rc<Span>>, input: &str, offset: usize) -> Vec<Span> {
+        let rest = &input[offset..];
+        // Lexeme lengths and captures for each terminal, shared by all items
+        // waiting on it
+        let mut lexemes: HashMap<&str, Vec<(usize, Capture)>> = HashMap::new();
+        let mut new_items = Vec::new();
+        for span in stateset {
+            let Some(symbol @ Symbol::Term(name, _)) = span.next_symbol() else {
+                continue;
+            };
+            let lexemes = lexemes
+                .entry(name)
+                .or_insert_with(|| match self.grammar.pattern(name) {
+                    Some(pattern) => {
+                        let capture = Capture(Rc::new(()));
+                        let lengths = pattern.prefix_matches(rest).into_iter();
+                        lengths.map(|len| (len, capture.clone())).collect()
+                    }
+                    None => (rest.char_indices().skip(1).map(|(i, _)| i))
+                        .chain([rest.len()])
+                        .filter_map(|i| Some((i, Capture(symbol.capture(&rest[..i])?.into()))))
+                        .collect(),
+                });
+            // Empty lexemes aren't scanned, use a nullable rule instead
+            for (len, capture) in lexemes.iter().filter(|(len, _)| *len > 0) {
+                new_items.push(Span::extend(
+                    SpanSource::Scan(span.clone(), rest[..*len].to_string(), capture.clone()),
+                    offset + len,
+                ));
+            }
+        }
+        new_items
+    }
+
+    /// Parse raw text, no tokenizer needed: terminals scan their lexemes
+    /// straight from `input` and may span several chars. Positions in the
+    /// resulting Spans are byte offsets so each lexeme is `input[start..end]`.
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashSet is sound
+    #[allow(clippy::mutable_key_type)]
+    pub fn parse_str(&self, input: &str) -> Result<ParseTrees, ParseError> {
+        let mut session = self.session();
+        // Scanned items waiting for the parse to reach the offset they end at
+        let mut scanned = vec![HashSet::<Rc<Span>>::new(); input.len() + 1];
+        let mut furthest = 0;
+        for offset in 0..input.len() {
+            // StateSets in the middle of a multi-byte char stay empty
+            if input.is_char_boundary(offset) {
+                for new_item in self.scans_str(session.last_stateset(), input, offset) {
+                    furthest = furthest.max(new_item.end);
+                    let stateset = &mut scanned[new_item.end];
                     if let Some(existent) = stateset.get(&new_item) {
                         existent.merge_sources(new_item);
                     } else {
                         stateset.insert(Rc::new(new_item));
                     }
                 }
-                // do precitions/completions until expansions are exhausted
-                if prev_len == stateset.len() {
-                    break;
-                }
             }
-            // Build Si+1 with items in the current state that accept the next token
-            if let Some(lexeme) = tokenizer.next() {
-                statesets.push(
-                    EarleyParser::scans(statesets[idx].iter(), lexeme.as_ref(), idx + 1).collect(),
-                );
-            } else {
+            // Nothing scanned reaches past this point, the input is rejected
+            if furthest <= offset {
+                let lexeme = input[offset..].chars().next().map(|c| c.to_string());
+                return Err(ParseError {
+                    in_bytes: true,
+                    ..EarleyParser::parse_error(session.last_stateset().iter(), offset, lexeme)
+                });
+            }
+            session.advance(std::mem::take(&mut scanned[offset + 1]), &mut ());
+        }
+        Self::finish(&session).map_err(|error| ParseError {
+            in_bytes: true,
+            ..error
+        })
+    }
+
+    // Longest non-empty parse of tokens from `origin`, feeding them until
+    // one is rejected. Shorter parses are dropped as longer ones complete.
+    fn longest_from(&self, tokens: &[String], origin: usize) -> Option<PartialParse> {
+        let mut session =
+            ParseSession::starting_at(self, &self.grammar.start, Some(origin), &mut ());
+        let mut longest = None;
+        for lexeme in &tokens[origin..] {
+            if session.feed(lexeme).is_err() {
                 break;
             }
+            if let Ok(trees) = session.parse_trees() {
+                longest = Some(PartialParse {
+                    start: origin,
+                    end: session.position(),
+                    trees,
+                });
+            }
         }
+        longest
+    }
+
+    /// Recover from input that doesn't fully parse: longest complete parse
+    /// of the start symbol covering a (non-empty) prefix of the input.
+    pub fn parse_prefix<T>(&self, tokenizer: T) -> Option<PartialParse>
+    where
+        T: Iterator,
+        T::Item: Debug + AsRef<str>,
+    {
+        let tokens: Vec<_> = tokenizer.map(|t| t.as_ref().to_string()).collect();
+        self.longest_from(&tokens, 0)
+    }
 
+    /// Find the parses of the start symbol embedded in the input, eg: dates
+    /// in a sentence. See `find_maximal`.
+    pub fn parse_substrings<T>(&self, tokenizer: T) -> Vec<PartialParse>
+    where
+        T: Iterator,
+        T::Item: Debug + AsRef<str>,
+    {
+        self.find_maximal(&self.grammar.start, tokenizer)
+    }
+
+    /// Every non-empty span of the input where `symbol` completes, nested
+    /// and overlapping ones included, sorted by position. They all come out
+    /// of a single chart where `symbol` is predicted at every token.
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashSet is sound
+    #[allow(clippy::mutable_key_type)]
+    pub fn find_all<T>(&self, symbol: &str, tokenizer: T) -> Vec<PartialParse>
+    where
+        T: Iterator,
+        T::Item: Debug + AsRef<str>,
+    {
+        let mut session = ParseSession::starting_at(self, symbol, None, &mut ());
+        for lexeme in tokenizer {
+            // Tokens nobody scans are fine, there's a new parse at each token
+            let next_stateset = Self::scans(
+                session.last_stateset().iter(),
+                lexeme.as_ref(),
+                session.statesets.len(),
+            )
+            .collect();
+            session.advance(next_stateset, &mut ());
+        }
+        let mut found = Vec::new();
+        for (end, stateset) in session.statesets.iter().enumerate() {
+            // Spans with the same start and end are alternative parses
+            let mut completed = BTreeMap::<usize, Vec<Rc<Span>>>::new();
+            for item in stateset {
+                if item.complete() && Some(item.rule.head_id) == session.root && item.start < end {
+                    completed.entry(item.start).or_default().push(item.clone());
+                }
+            }
+            for (start, trees) in completed {
+                found.push(PartialParse {
+                    start,
+                    end,
+                    trees: ParseTrees(trees),
+                });
+            }
+        }
+        found.sort_by_key(|parse| (parse.start, parse.end));
+        found
+    }
+
+    /// Spans of the input where `symbol` completes that don't overlap.
+    /// Scans left to right taking the longest span at each token, then
+    /// carries on after it.
+    pub fn find_maximal<T>(&self, symbol: &str, tokenizer: T) -> Vec<PartialParse>
+    where
+        T: Iterator,
+        T::Item: Debug + AsRef<str>,
+    {
+        let mut found = self.find_all(symbol, tokenizer);
+        found.sort_by_key(|parse| (parse.start, Reverse(parse.end)));
+        let mut taken = 0;
+        found.retain(|parse| {
+            let keep = parse.start >= taken;
+            if keep {
+                taken = parse.end;
+            }
+            keep
+        });
+        found
+    }
+
+    /// Parse skipping the tokens the grammar doesn't accept where they show
+    /// up. Returns the parse trees and the indexes of the skipped tokens.
+    /// Skipping is greedy, and Span positions only count accepted tokens.
+    pub fn parse_skipping<T>(&self, tokenizer: T) -> Result<(ParseTrees, Vec<usize>), ParseError>
+    where
+        T: Iterator,
+        T::Item: Debug + AsRef<str>,
+    {
+        let mut session = self.session();
+        let mut skipped = Vec::new();
+        for (idx, lexeme) in tokenizer.enumerate() {
+            if session.feed(lexeme.as_ref()).is_err() {
+                skipped.push(idx);
+            }
+        }
+        Ok((Self::finish(&session)?, skipped))
+    }
+
+    // Collect the parse trees once all input has been consumed
+    fn finish(session: &ParseSession) -> Result<ParseTrees, ParseError> {
         // debug StateSets
         if cfg!(feature = "debug") {
-            for (idx, stateset) in statesets.iter().enumerate() {
+            for (idx, stateset) in session.statesets.iter().enumerate() {
                 eprintln!("=== StateSet {} ===", idx);
                 stateset
                     .iter()
@@ -153,27 +492,225 @@ impl EarleyParser {
             }
         }
 
-        // Check that at least one item is a. complete, b. starts at the idx 0,
-        // and c. the name of the rule matches the starting symbol.
+        let parse_trees = session.parse_trees()?;
+        if cfg!(feature = "debug") {
+            eprintln!("=== Parse Chart ===");
+            for t in &parse_trees.0 {
+                eprintln!("{}", t.stringify(0));
+            }
+        }
+        Ok(parse_trees)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// A resumable parse where input is fed one token at a time. Tokens that
+/// don't fit the grammar are rejected, so the input fed so far is always a
+/// viable prefix. Cloning a session is cheap (Spans are shared) which is
+/// handy to snapshot the chart or fork it to try alternative inputs.
+#[derive(Clone)]
+pub struct ParseSession<'a> {
+    parser: &'a EarleyParser,
+    // Symbol parsed and the token index where its parse begins. Without
+    // an origin the symbol is predicted anew at every StateSet. There's
+    // no root if the symbol isn't in the grammar, nothing parses then.
+    root: Option<SymbolId>,
+    origin: Option<usize>,
+    statesets: Vec<HashSet<Rc<Span>>>,
+    // Deterministic reduction paths found so far, per StateSet and symbol
+    leo_memo: HashMap<(usize, SymbolId), Option<Rc<LeoItem>>>,
+}
+
+// Spans hash/eq ignoring their RefCell'd backpointers so HashSet is sound
+#[allow(clippy::mutable_key_type)]
+impl<'a> ParseSession<'a> {
+    // Session parsing `root` after skipping `origin` tokens. Skipped
+    // StateSets stay empty so Span positions are indexes in the whole input.
+    fn starting_at(
+        parser: &'a EarleyParser,
+        root: &str,
+        origin: Option<usize>,
+        observer: &mut dyn ParseObserver,
+    ) -> ParseSession<'a> {
+        let mut session = ParseSession {
+            parser,
+            root: parser.grammar.symbol_id(root),
+            origin,
+            statesets: vec![HashSet::new(); origin.unwrap_or(0) + 1],
+            leo_memo: HashMap::new(),
+        };
+        session.seed(observer);
+        session.predict_complete(observer);
+        session
+    }
+
+    // Add items for each rule of the root symbol to the last StateSet
+    fn seed(&mut self, observer: &mut dyn ParseObserver) {
+        let idx = self.statesets.len() - 1;
+        let Some(root) = self.root else {
+            return;
+        };
+        for rule in self.parser.grammar.rules_for(root) {
+            if self.statesets[idx].insert(Rc::new(Span::new(rule, idx))) {
+                observer.predict(rule, idx);
+            }
+        }
+    }
+
+    // Predict/Complete each Span once as they're added to the last StateSet.
+    // Nullable symbols are completed on prediction so no need to revisit.
+    fn predict_complete(&mut self, observer: &mut dyn ParseObserver) {
+        let idx = self.statesets.len() - 1;
+        let mut pending: Vec<_> = self.statesets[idx].iter().cloned().collect();
+        while let Some(trigger) = pending.pop() {
+            let new_items = match trigger.next_symbol() {
+                Some(Symbol::NonTerm(_)) => {
+                    let symbol = trigger.rule.spec_ids[trigger.dot];
+                    self.parser
+                        .predictions(&self.statesets[idx], &trigger, symbol)
+                }
+                Some(Symbol::Term(_, _)) => {
+                    // Scan items populate next stateset only when done with current state
+                    continue;
+                }
+                None => {
+                    observer.complete(&trigger.rule, trigger.start, idx);
+                    // Right recursion: complete the top of the reduction path
+                    // right away instead of walking it one Span at a time
+                    let leo = if trigger.start < idx {
+                        EarleyParser::leo_item(
+                            &self.statesets,
+                            &mut self.leo_memo,
+                            (self.root, self.origin),
+                            trigger.start,
+                            trigger.rule.head_id,
+                        )
+                    } else {
+                        None
+                    };
+                    match leo {
+                        Some(leo) => vec![Span::leo_complete(leo, trigger.clone(), idx)],
+                        None => EarleyParser::completions(
+                            self.statesets[trigger.start].iter(),
+                            &trigger,
+                            idx,
+                        )
+                        .collect(),
+                    }
+                }
+            };
+            let stateset = self.statesets.get_mut(idx).unwrap();
+            // Add new items to the current stateset merging existing ones
+            for new_item in new_items {
+                if let Some(existent) = stateset.get(&new_item) {
+                    existent.merge_sources(new_item);
+                } else {
+                    if new_item.dot == 0 {
+                        observer.predict(&new_item.rule, idx);
+                    }
+                    let new_item = Rc::new(new_item);
+                    stateset.insert(new_item.clone());
+                    pending.push(new_item);
+                }
+            }
+        }
+        observer.stateset(idx, self.statesets[idx].len());
+    }
+
+    fn last_stateset(&self) -> &HashSet<Rc<Span>> {
+        self.statesets.last().expect("No Statesets (even s0)")
+    }
+
+    /// Number of tokens fed so far
+    pub fn position(&self) -> usize {
+        self.statesets.len() - 1
+    }
+
+    /// Feed the next token. If no item accepts it the session is left
+    /// untouched and the error reports what was expected instead.
+    pub fn feed(&mut self, lexeme: &str) -> Result<(), ParseError> {
+        self.feed_observed(lexeme, &mut ())
+    }
+
+    fn feed_observed(
+        &mut self,
+        lexeme: &str,
+        observer: &mut dyn ParseObserver,
+    ) -> Result<(), ParseError> {
+        // Build Si+1 with items in the current state that accept the next token
+        let next_stateset: HashSet<_> =
+            EarleyParser::scans(self.last_stateset().iter(), lexeme, self.statesets.len())
+                .collect();
+        // No item accepted the lexeme, there's no point consuming more input
+        if next_stateset.is_empty() {
+            return Err(EarleyParser::parse_error(
+                self.last_stateset().iter(),
+                self.position(),
+                Some(lexeme.to_string()),
+            ));
+        }
+        for span in &next_stateset {
+            observer.scan(&span.rule, lexeme, span.end);
+        }
+        self.advance(next_stateset, observer);
+        Ok(())
+    }
+
+    // Move on to the next StateSet seeded with already scanned items
+    fn advance(&mut self, next_stateset: HashSet<Rc<Span>>, observer: &mut dyn ParseObserver) {
+        self.statesets.push(next_stateset);
+        if self.origin.is_none() {
+            self.seed(observer);
+        }
+        self.predict_complete(observer);
+    }
+
+    /// Check if feeding `lexeme` would keep the input a viable prefix
+    pub fn accepts(&self, lexeme: &str) -> bool {
+        self.last_stateset()
+            .iter()
+            .any(|span| span.next_symbol().is_some_and(|s| s.matches(lexeme)))
+    }
+
+    /// Names of the terminals that could follow the input fed so far
+    pub fn expected(&self) -> Vec<String> {
+        EarleyParser::parse_error(self.last_stateset().iter(), self.position(), None).expected
+    }
+
+    /// Check if the input fed so far is already a complete parse
+    pub fn is_complete(&self) -> bool {
+        self.last_stateset().iter().any(|item| self.is_root(item))
+    }
+
+    // Check that the item is a. complete, b. starts at the session origin,
+    // and c. the name of the rule matches the starting symbol.
+    fn is_root(&self, item: &Span) -> bool {
+        Some(item.start) == self.origin && item.complete() && Some(item.rule.head_id) == self.root
+    }
+
+    /// Parse trees for the input fed so far
+    pub fn parse_trees(&self) -> Result<ParseTrees, ParseError> {
         // It spans the whole input because we search at the last stateset
-        let parse_trees: Vec<_> = statesets
-            .pop()
-            .expect("No Statesets (even s0)")
+        let parse_trees: Vec<_> = self
+            .last_stateset()
             .iter()
-            .filter(|item| {
-                item.start == 0 && item.complete() && item.rule.head == self.grammar.start
-            })
+            .filter(|item| self.is_root(item))
             .cloned()
             .collect();
         if parse_trees.is_empty() {
-            return Err("Parse Error: No Rule completes".to_string());
-        }
-        if cfg!(feature = "debug") {
-            eprintln!("=== Parse Chart ===");
-            for t in &parse_trees {
-                eprintln!("{}", t.stringify(0));
-            }
+            return Err(EarleyParser::parse_error(
+                self.last_stateset().iter(),
+                self.position(),
+                None,
+            ));
         }
         Ok(ParseTrees(parse_trees))
     }
+
+    /// Graphviz DOT of the chart built so far: every Span of every StateSet
+    /// and the backpointers that led to it.
+    pub fn to_dot(&self) -> String {
+        spans_to_dot(self.statesets.iter().flatten())
+    }
 }
@@ -15,10 +15,8 @@ enum Tree {
 
 fn tree_evaler<'a>(g: Grammar) -> EarleyForest<'a, Tree> {
     let mut evaler = EarleyForest::new(|sym, tok| Tree::Leaf(sym.to_string(), tok.to_string()));
-    for rule in g.rules {
-        evaler.action(&rule.to_string(), move |nodes| {
-            Tree::Node(rule.to_string(), nodes)
-        });
+    for rule in g.rules().iter().map(|r| r.to_string()) {
+        evaler.action(&rule.clone(), move |nodes| Tree::Node(rule.clone(), nodes));
     }
     evaler
 }
@@ -197,6 +195,64 @@ fn grammar_ambiguous() {
     check_trees(&trees2, expected_trees);
 }
 
+#[test]
+fn nullable_trees() {
+    // Nullable symbols are completed at prediction, trees must still unwind.
+    // S -> A A x ; A -> B | a ; B -> <e>
+    let grammar = GrammarBuilder::default()
+        .nonterm("S")
+        .nonterm("A")
+        .nonterm("B")
+        .terminal("a", |n| n == "a")
+        .terminal("x", |n| n == "x")
+        .rule("S", &["A", "A", "x"])
+        .rule("A", &["B"])
+        .rule("A", &["a"])
+        .rule("B", &[])
+        .into_grammar("S")
+        .expect("Bad grammar");
+    let p = EarleyParser::new(grammar.clone());
+    let evaler = tree_evaler(grammar);
+
+    let pout = p.parse("x".split_whitespace()).unwrap();
+    let expected_trees = vec![concat!(
+        r#"Node("S -> A A x", ["#,
+        r#"Node("A -> B", [Node("B -> ", [])]), "#,
+        r#"Node("A -> B", [Node("B -> ", [])]), "#,
+        r#"Leaf("x", "x")])"#
+    )];
+    check_trees(
+        &evaler.eval_all_recursive(&pout).unwrap(),
+        expected_trees.clone(),
+    );
+    check_trees(&evaler.eval_all(&pout).unwrap(), expected_trees.clone());
+    let lazy = evaler.eval_iter(&pout).collect::<Result<Vec<_>, _>>();
+    check_trees(&lazy.unwrap(), expected_trees);
+
+    let pout = p.parse("a x".split_whitespace()).unwrap();
+    let expected_trees = vec![
+        concat!(
+            r#"Node("S -> A A x", ["#,
+            r#"Node("A -> a", [Leaf("a", "a")]), "#,
+            r#"Node("A -> B", [Node("B -> ", [])]), "#,
+            r#"Leaf("x", "x")])"#
+        ),
+        concat!(
+            r#"Node("S -> A A x", ["#,
+            r#"Node("A -> B", [Node("B -> ", [])]), "#,
+            r#"Node("A -> a", [Leaf("a", "a")]), "#,
+            r#"Leaf("x", "x")])"#
+        ),
+    ];
+    check_trees(
+        &evaler.eval_all_recursive(&pout).unwrap(),
+        expected_trees.clone(),
+    );
+    check_trees(&evaler.eval_all(&pout).unwrap(), expected_trees.clone());
+    let lazy = evaler.eval_iter(&pout).collect::<Result<Vec<_>, _>>();
+    check_trees(&lazy.unwrap(), expected_trees);
+}
+
 #[test]
 fn earley_corner_case() {
     // Earley's corner case. We should only get 2 trees.
@@ -237,6 +293,52 @@ fn earley_corner_case() {
     check_trees(&evaler.eval_all(&pout).unwrap(), expected_trees.clone());
 }
 
+#[test]
+fn right_recursion_leo() {
+    // Leo items skip intermediate completions, trees should still have them.
+    // T -> S ; S -> a S | a
+    let grammar = GrammarBuilder::default()
+        .nonterm("T")
+        .nonterm("S")
+        .terminal("a", |n| n == "a")
+        .rule("T", &["S"])
+        .rule("S", &["a", "S"])
+        .rule("S", &["a"])
+        .into_grammar("T")
+        .expect("Bad grammar");
+    let p = EarleyParser::new(grammar.clone());
+    let pout = p.parse("a a a".split_whitespace()).unwrap();
+
+    let expected_trees = vec![concat!(
+        r#"Node("T -> S", ["#,
+        r#"Node("S -> a S", [Leaf("a", "a"), "#,
+        r#"Node("S -> a S", [Leaf("a", "a"), "#,
+        r#"Node("S -> a", [Leaf("a", "a")])])])])"#
+    )];
+    let evaler = tree_evaler(grammar.clone());
+    check_trees(
+        &evaler.eval_all_recursive(&pout).unwrap(),
+        expected_trees.clone(),
+    );
+    check_trees(&evaler.eval_all(&pout).unwrap(), expected_trees);
+
+    // Start symbol is right recursive itself
+    let grammar = GrammarBuilder::default()
+        .nonterm("S")
+        .terminal("a", |n| n == "a")
+        .rule("S", &["a", "S"])
+        .rule("S", &["a"])
+        .into_grammar("S")
+        .expect("Bad grammar");
+    let mut ev = EarleyForest::new(|_, _| 1);
+    ev.action("S -> a S", |n| n[0] + n[1]);
+    ev.action("S -> a", |n| n[0]);
+    let input = vec!["a"; 1000];
+    let pout = EarleyParser::new(grammar).parse(input.iter()).unwrap();
+    assert_eq!(ev.eval(&pout), Ok(1000));
+    assert_eq!(ev.eval_iter(&pout).collect::<Vec<_>>(), vec![Ok(1000)]);
+}
+
 #[test]
 fn earley_bottomless() {
     // E -> A ; A -> E | n
@@ -258,9 +360,39 @@ fn earley_bottomless() {
         &evaler.eval_all_recursive(&pout).unwrap(),
         expected_trees.clone(),
     );
+    // The E -> A -> E cycle is skipped
+    let lazy = evaler.eval_iter(&pout).collect::<Result<Vec<_>, _>>();
+    check_trees(&lazy.unwrap(), expected_trees);
     // TODO: check_trees(&evaler.eval_all(&pout).unwrap(), expected_trees.clone());
 }
 
+#[test]
+fn lazy_trees_skip_cycles() {
+    // S -> S S | S | n
+    let grammar = GrammarBuilder::default()
+        .nonterm("S")
+        .terminal("n", |n| n == "n")
+        .rule("S", &["S", "S"])
+        .rule("S", &["S"])
+        .rule("S", &["n"])
+        .into_grammar("S")
+        .expect("Bad grammar");
+    let mut ev = EarleyForest::new(|_, n| n.to_string());
+    ev.action("S -> S S", |n| format!("({} {})", n[0], n[1]));
+    ev.action("S -> S", |n| format!("[{}]", n[0]));
+    ev.action("S -> n", |n| n[0].clone());
+    let pout = EarleyParser::new(grammar)
+        .parse("n n n".split_whitespace())
+        .unwrap();
+    // Infinitely many trees nest S -> S, only those without cycles: each
+    // of the 5 nodes of both bracketings is wrapped in S -> S at most once
+    let trees: Vec<_> = ev.eval_iter(&pout).map(Result::unwrap).collect();
+    assert_eq!(trees.len(), 2 * 32);
+    assert!(trees.contains(&"((n n) n)".to_string()));
+    assert!(trees.contains(&"[(n [(n n)])]".to_string()));
+    assert!(trees.iter().all(|tree| !tree.contains("[[")));
+}
+
 #[test]
 fn grammar_ambiguous_epsilon() {
     // S -> SSX | b
@@ -304,6 +436,8 @@ fn grammar_ambiguous_epsilon() {
         expected_trees.clone(),
     );
     check_trees(&evaler.eval_all(&pout).unwrap(), expected_trees.clone());
+    let lazy = evaler.eval_iter(&pout).collect::<Result<Vec<_>, _>>();
+    check_trees(&lazy.unwrap(), expected_trees);
 }
 
 #[test]
@@ -399,6 +533,10 @@ fn math_ambiguous_catalan() {
     // https://en.wikipedia.org/wiki/Associahedron
     assert_eq!(ef.eval_all_recursive(&pout).unwrap().len(), 42);
     assert_eq!(ef.eval_all(&pout).unwrap().len(), 42);
+    assert_eq!(ef.eval_iter(&pout).count(), 42);
+    // Trees are built as they're requested
+    let first: Vec<_> = ef.eval_iter(&pout).take(3).collect();
+    assert_eq!(first.len(), 3);
 }
 
 #[test]
@@ -420,6 +558,7 @@ fn trigger_has_multiple_bp() {
     let ef = tree_evaler(grammar);
     assert_eq!(ef.eval_all_recursive(&pout).unwrap().len(), 8);
     assert_eq!(ef.eval_all(&pout).unwrap().len(), 8);
+    assert_eq!(ef.eval_iter(&pout).count(), 8);
 }
 
 mod small_math {
@@ -542,16 +681,56 @@ mod small_math {
 
 mod earley_recognizer {
     use super::super::grammar::GrammarBuilder;
-    use super::super::EarleyParser;
+    use super::super::{EarleyParser, ParseError};
 
     fn good(parser: &EarleyParser, input: &str) {
         assert!(parser.parse(input.split_whitespace()).is_ok());
     }
 
     fn fail(parser: &EarleyParser, input: &str) {
+        let error = parser.parse(input.split_whitespace()).unwrap_err();
+        assert!(error.to_string().starts_with("Parse Error: unexpected"));
+    }
+
+    #[test]
+    fn parse_error_position() {
+        // Time -> Day of Month | Day after Time
+        let grammar = GrammarBuilder::default()
+            .nonterm("Time")
+            .terminal("Day", |n| n == "monday" || n == "friday")
+            .terminal("Month", |n| n == "march")
+            .terminal("of", |n| n == "of")
+            .terminal("after", |n| n == "after")
+            .rule("Time", &["Day", "of", "Month"])
+            .rule("Time", &["Day", "after", "Time"])
+            .into_grammar("Time")
+            .expect("Bad Grammar");
+        let p = EarleyParser::new(grammar);
+        // Input fails midway
+        let error = p.parse("friday after monday in march".split_whitespace());
+        assert_eq!(
+            error.unwrap_err(),
+            ParseError {
+                position: 3,
+                in_bytes: false,
+                lexeme: Some("in".to_string()),
+                expected: vec!["after".to_string(), "of".to_string()],
+            }
+        );
+        // Input ends prematurely
+        let error = p.parse("friday of".split_whitespace()).unwrap_err();
+        assert_eq!(error.position, 2);
+        assert_eq!(error.lexeme, None);
+        assert_eq!(error.expected, vec!["Month".to_string()]);
         assert_eq!(
-            parser.parse(input.split_whitespace()).unwrap_err(),
-            "Parse Error: No Rule completes"
+            error.to_string(),
+            "Parse Error: unexpected end of input at token 2, expected one of `Month`"
+        );
+        // Error converts into String for callers bubbling up String errors
+        let error: String = p.parse("march".split_whitespace()).unwrap_err().into();
+        assert_eq!(
+            error,
+            "Parse Error: unexpected `march` at token 0, expected one of `Day`"
         );
     }
 
@@ -753,3 +932,721 @@ mod earley_recognizer {
         assert!(p.parse(input).is_ok());
     }
 }
+
+#[test]
+fn precedence_associativity() {
+    use super::grammar::Assoc;
+    // E -> E + E | E - E (left 1) ; E -> E * E (left 2) ; E -> E ^ E (right 3)
+    // E -> - E (right 4) ; E -> E < E (nonassoc 0) ; E -> n
+    let grammar = GrammarBuilder::default()
+        .nonterm("E")
+        .terminal("n", |n| n.chars().all(|c| c.is_ascii_digit()))
+        .terminal("+", |n| n == "+")
+        .terminal("-", |n| n == "-")
+        .terminal("*", |n| n == "*")
+        .terminal("^", |n| n == "^")
+        .terminal("<", |n| n == "<")
+        .rule_prec("E", &["E", "<", "E"], 0, Assoc::NonAssoc)
+        .rule_prec("E", &["E", "+", "E"], 1, Assoc::Left)
+        .rule_prec("E", &["E", "-", "E"], 1, Assoc::Left)
+        .rule_prec("E", &["E", "*", "E"], 2, Assoc::Left)
+        .rule_prec("E", &["E", "^", "E"], 3, Assoc::Right)
+        .rule_prec("E", &["-", "E"], 4, Assoc::Right)
+        .rule("E", &["n"])
+        .into_grammar("E")
+        .expect("Bad grammar");
+    let parser = EarleyParser::new(grammar);
+    let mut ev = EarleyForest::new(|_, tok| tok.parse::<i64>().unwrap_or(0));
+    ev.action("E -> E < E", |n| (n[0] < n[2]) as i64);
+    ev.action("E -> E + E", |n| n[0] + n[2]);
+    ev.action("E -> E - E", |n| n[0] - n[2]);
+    ev.action("E -> E * E", |n| n[0] * n[2]);
+    ev.action("E -> E ^ E", |n| n[0].pow(n[2] as u32));
+    ev.action("E -> - E", |n| -n[1]);
+    ev.action("E -> n", |n| n[0]);
+
+    for (input, value) in [
+        ("1 + 2 * 3 - 4", 3),
+        ("8 - 4 - 2", 2),
+        ("2 ^ 3 ^ 2", 512),
+        ("- 2 ^ 2", 4),
+        ("2 * - 3 + 1", -5),
+        ("1 + 1 < 3", 1),
+    ] {
+        let trees = parser.parse(input.split_whitespace()).unwrap();
+        assert_eq!(ev.eval_all(&trees), Ok(vec![value]), "{}", input);
+        // Every tree consumer sees the pruned forest
+        assert_eq!(ev.eval(&trees), Ok(value));
+        assert_eq!(ev.eval_recursive(&trees), Ok(value));
+        assert_eq!(ev.eval_all_recursive(&trees), Ok(vec![value]));
+        assert_eq!(ev.eval_iter(&trees).collect::<Vec<_>>(), vec![Ok(value)]);
+        assert_eq!(ev.eval_k_best(&trees, 5), Ok(vec![(value, 1.0)]));
+        assert_eq!(super::sppf::Sppf::new(&trees).count_trees(), 1);
+    }
+    // Non-associative operators can't chain, the input parses with no tree
+    let trees = parser.parse("1 < 2 < 3".split_whitespace()).unwrap();
+    assert_eq!(ev.eval_all(&trees), Ok(vec![]));
+    assert_eq!(ev.eval_iter(&trees).count(), 0);
+    assert_eq!(
+        ev.eval(&trees),
+        Err("Precedence filters rejected every parse tree".to_string())
+    );
+    assert_eq!(super::sppf::Sppf::new(&trees).count_trees(), 0);
+}
+
+#[test]
+fn parse_str_scannerless() {
+    // S -> S + num | S - id | num ; with multi-char terminals
+    let grammar = GrammarBuilder::default()
+        .nonterm("S")
+        .pattern("num", "[0-9]+")
+        .pattern("+", r"\s*\+\s*")
+        .terminal("-", |s| s == "-")
+        .terminal("id", |s| s.chars().all(char::is_alphabetic))
+        .rule("S", &["S", "+", "num"])
+        .rule("S", &["S", "-", "id"])
+        .rule("S", &["num"])
+        .into_grammar("S")
+        .expect("Bad grammar");
+    let parser = EarleyParser::new(grammar);
+    let mut ev = EarleyForest::new(|sym, tok| match sym {
+        "num" => tok.parse().unwrap(),
+        "id" => tok.chars().count(),
+        _ => 0,
+    });
+    ev.action("S -> S + num", |n| n[0] + n[2]);
+    ev.action("S -> S - id", |n| n[0] - n[2]);
+    ev.action("S -> num", |n| n[0]);
+
+    let trees = parser.parse_str("12 + 3+405-año").unwrap();
+    assert_eq!(ev.eval_all(&trees), Ok(vec![417]));
+    // Spans are byte offsets into the input
+    assert_eq!((trees.0[0].start, trees.0[0].end), (0, 15));
+
+    let error = parser.parse_str("12 + x").unwrap_err();
+    assert_eq!(error.position, 5);
+    assert_eq!(error.lexeme, Some("x".to_string()));
+    assert_eq!(error.expected, vec!["num"]);
+    assert_eq!(
+        error.to_string(),
+        "Parse Error: unexpected `x` at byte 5, expected one of `num`"
+    );
+    let error = parser.parse_str("12 +").unwrap_err();
+    assert!(error.in_bytes);
+    assert_eq!((error.position, error.lexeme), (4, None));
+    assert!(parser.parse_str("").is_err());
+}
+
+#[test]
+fn captures_kept_from_parse() {
+    // Captures run while scanning, evaluation gets the values they returned
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let grammar = GrammarBuilder::default()
+        .nonterm("S")
+        .terminal_capture("n", move |s| {
+            let n = s.parse::<u32>().ok()?;
+            Some(n * 10 + counter.fetch_add(1, Ordering::SeqCst) as u32 % 2)
+        })
+        .terminal("+", |s| s == "+")
+        .rule("S", &["S", "+", "n"])
+        .rule("S", &["n"])
+        .into_grammar("S")
+        .expect("Bad grammar");
+    let parser = EarleyParser::new(grammar);
+    let mut ev = EarleyForest::new_with_capture(|_, _, value| {
+        value.downcast_ref::<u32>().copied().unwrap_or(0)
+    });
+    ev.action("S -> S + n", |n| n[0] + n[2]);
+    ev.action("S -> n", |n| n[0]);
+
+    let trees = parser.parse("1 + 2".split_whitespace()).unwrap();
+    let scanned = calls.load(Ordering::SeqCst);
+    assert_eq!(ev.eval(&trees), Ok(10 + 21));
+    assert_eq!(ev.eval_all(&trees), Ok(vec![10 + 21]));
+    let trees = parser.parse_str("3+4").unwrap();
+    let scanned = calls.load(Ordering::SeqCst) - scanned;
+    assert_eq!(scanned, 2);
+    assert_eq!(ev.eval_best(&trees), Ok(30 + 41));
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn actions_with_span() {
+    // Build a tree where every node knows the input range it covers
+    let grammar = GrammarBuilder::default()
+        .nonterm("S")
+        .nonterm("O")
+        .pattern("n", "[0-9]+")
+        .terminal("+", |s| s == "+")
+        .rule("S", &["S", "+", "n", "O"])
+        .rule("S", &["n"])
+        .rule("O", &[])
+        .into_grammar("S")
+        .expect("Bad grammar");
+    let parser = EarleyParser::new(grammar);
+    let mut ev = EarleyForest::new_with_span(|sym, tok, start, end| {
+        format!("{}:{}@{}..{}", sym, tok, start, end)
+    });
+    ev.action_with_span("S -> S + n O", |n, start, end| {
+        format!("({} {} {} {})@{}..{}", n[0], n[1], n[2], n[3], start, end)
+    });
+    ev.action_with_span("O -> ", |_, start, end| format!("O@{}..{}", start, end));
+    ev.action("S -> n", |mut n| n.remove(0));
+
+    let expected = "(n:1@0..1 +:+@1..2 n:2@2..3 O@3..3)@0..3";
+    let trees = parser.parse("1 + 2".split_whitespace()).unwrap();
+    assert_eq!(ev.eval(&trees).unwrap(), expected);
+    assert_eq!(ev.eval_all(&trees).unwrap(), vec![expected]);
+    assert_eq!(ev.eval_recursive(&trees).unwrap(), expected);
+    assert_eq!(ev.eval_all_recursive(&trees).unwrap(), vec![expected]);
+
+    // With parse_str positions are byte offsets
+    let trees = parser.parse_str("10+200+3").unwrap();
+    assert_eq!(
+        ev.eval(&trees).unwrap(),
+        "((n:10@0..2 +:+@2..3 n:200@3..6 O@6..6)@0..6 +:+@6..7 n:3@7..8 O@8..8)@0..8"
+    );
+}
+
+#[test]
+fn action_coverage() {
+    // E -> E + T | T ; T -> n | ( E )
+    let grammar = GrammarBuilder::default()
+        .nonterm("E")
+        .nonterm("T")
+        .terminal("n", |n| n.chars().all(|c| c.is_ascii_digit()))
+        .terminal("+", |n| n == "+")
+        .terminal("(", |n| n == "(")
+        .terminal(")", |n| n == ")")
+        .rule("E", &["E", "+", "T"])
+        .rule("E", &["T"])
+        .rule("T", &["n"])
+        .rule("T", &["(", "E", ")"])
+        .into_grammar("E")
+        .expect("Bad grammar");
+    let mut ev = EarleyForest::new(|_, tok| tok.parse().unwrap_or(0));
+    ev.action("E -> E + T", |n| n[0] + n[2]);
+    ev.action("T -> ( E)", |n| n[1]);
+    assert_eq!(
+        ev.missing_actions(&grammar),
+        vec!["E -> T", "T -> n", "T -> ( E )"]
+    );
+    assert_eq!(ev.unknown_actions(&grammar), vec!["T -> ( E)"]);
+    assert_eq!(
+        ev.validate(&grammar),
+        Err(concat!(
+            "Missing Action: E -> T\n",
+            "Missing Action: T -> n\n",
+            "Missing Action: T -> ( E )\n",
+            "Unknown Action: T -> ( E)"
+        )
+        .to_string())
+    );
+
+    // Single child rules pass their value through
+    ev.passthrough();
+    ev.action("T -> ( E )", |n| n[1]);
+    assert_eq!(ev.missing_actions(&grammar), Vec::<String>::new());
+    let trees = EarleyParser::new(grammar.clone())
+        .parse("1 + ( 2 + 3 )".split_whitespace())
+        .unwrap();
+    assert_eq!(ev.eval(&trees), Ok(6));
+
+    // Default action covers all other rules
+    let mut ev = EarleyForest::new(|_, tok| tok.parse().unwrap_or(0));
+    ev.default_action(|n| n.iter().sum());
+    assert!(ev.validate(&grammar).is_ok());
+    assert_eq!(ev.eval(&trees), Ok(6));
+    assert_eq!(ev.eval_recursive(&trees), Ok(6));
+}
+
+#[test]
+fn partial_parses() {
+    // time -> next day | day ; day -> friday | monday
+    let grammar = GrammarBuilder::default()
+        .nonterm("time")
+        .nonterm("day")
+        .terminal("next", |n| n == "next")
+        .terminal("weekday", |n| n == "friday" || n == "monday")
+        .rule("time", &["next", "day"])
+        .rule("time", &["day"])
+        .rule("day", &["weekday"])
+        .into_grammar("time")
+        .expect("Bad grammar");
+    let ev = tree_evaler(grammar.clone());
+    let parser = EarleyParser::new(grammar);
+    let input = "remind me next friday at the office";
+    assert!(parser.parse(input.split_whitespace()).is_err());
+    assert!(parser.parse_prefix(input.split_whitespace()).is_none());
+    let prefix = parser
+        .parse_prefix("monday next".split_whitespace())
+        .unwrap();
+    assert_eq!((prefix.start, prefix.end), (0, 1));
+
+    // Leftmost longest, "friday" alone isn't reported
+    let found = parser.parse_substrings(input.split_whitespace());
+    assert_eq!(found.len(), 1);
+    assert_eq!((found[0].start, found[0].end), (2, 4));
+    let found = parser.parse_substrings("monday or next friday".split_whitespace());
+    let ranges: Vec<_> = found.iter().map(|p| (p.start, p.end)).collect();
+    assert_eq!(ranges, vec![(0, 1), (2, 4)]);
+    let next_friday = Tree::Node(
+        "time -> next day".to_string(),
+        vec![
+            Tree::Leaf("next".to_string(), "next".to_string()),
+            Tree::Node(
+                "day -> weekday".to_string(),
+                vec![Tree::Leaf("weekday".to_string(), "friday".to_string())],
+            ),
+        ],
+    );
+    assert_eq!(ev.eval(&found[1].trees), Ok(next_friday.clone()));
+
+    let (trees, skipped) = parser.parse_skipping(input.split_whitespace()).unwrap();
+    assert_eq!(skipped, vec![0, 1, 4, 5, 6]);
+    assert_eq!(ev.eval(&trees), Ok(next_friday));
+    let skipping = parser.parse_skipping("remind me next".split_whitespace());
+    assert!(skipping.is_err());
+}
+
+#[test]
+fn partial_parses_right_recursive() {
+    // S -> a S | a  (completed through Leo items from each origin)
+    let grammar = GrammarBuilder::default()
+        .nonterm("S")
+        .terminal("a", |n| n == "a")
+        .rule("S", &["a", "S"])
+        .rule("S", &["a"])
+        .into_grammar("S")
+        .expect("Bad grammar");
+    let parser = EarleyParser::new(grammar);
+    let found = parser.parse_substrings("b a a a b b a".split_whitespace());
+    let ranges: Vec<_> = found.iter().map(|p| (p.start, p.end)).collect();
+    assert_eq!(ranges, vec![(1, 4), (6, 7)]);
+    assert_eq!(found[0].trees.0.len(), 1);
+    let prefix = parser.parse_prefix("a a b a".split_whitespace()).unwrap();
+    assert_eq!((prefix.start, prefix.end), (0, 2));
+}
+
+#[test]
+fn find_all_spans() {
+    // S -> A S | A ; A -> a
+    let grammar = GrammarBuilder::default()
+        .nonterm("S")
+        .nonterm("A")
+        .terminal("a", |n| n == "a")
+        .rule("S", &["A", "S"])
+        .rule("S", &["A"])
+        .rule("A", &["a"])
+        .into_grammar("S")
+        .expect("Bad grammar");
+    let parser = EarleyParser::new(grammar);
+    let ranges = |found: Vec<super::parser::PartialParse>| {
+        found.iter().map(|p| (p.start, p.end)).collect::<Vec<_>>()
+    };
+    let found = parser.find_all("S", "a a b a".split_whitespace());
+    assert_eq!(ranges(found), vec![(0, 1), (0, 2), (1, 2), (3, 4)]);
+    let found = parser.find_all("A", "a a b a".split_whitespace());
+    assert_eq!(ranges(found), vec![(0, 1), (1, 2), (3, 4)]);
+    let found = parser.find_maximal("S", "b a a a b b a".split_whitespace());
+    assert_eq!(ranges(found), vec![(1, 4), (6, 7)]);
+    assert!(parser.find_all("Z", "a a".split_whitespace()).is_empty());
+
+    // Ambiguous spans hold all their parses
+    let grammar = GrammarBuilder::default()
+        .nonterm("E")
+        .terminal("n", |n| n == "n")
+        .terminal("+", |n| n == "+")
+        .rule("E", &["E", "+", "E"])
+        .rule("E", &["n"])
+        .into_grammar("E")
+        .expect("Bad grammar");
+    let ev = tree_evaler(grammar.clone());
+    let found = EarleyParser::new(grammar).find_maximal("E", "+ n + n + n +".split_whitespace());
+    assert_eq!(found.len(), 1);
+    assert_eq!((found[0].start, found[0].end), (1, 6));
+    assert_eq!(ev.eval_all(&found[0].trees).map(|t| t.len()), Ok(2));
+}
+
+#[test]
+fn weighted_best_parse() {
+    // PP attachment: "saw the man with the telescope"
+    let grammar = GrammarBuilder::default()
+        .nonterm("VP")
+        .nonterm("NP")
+        .nonterm("PP")
+        .terminal("V", |w| w == "saw")
+        .terminal("D", |w| w == "the")
+        .terminal("N", |w| w == "man" || w == "telescope")
+        .terminal("P", |w| w == "with")
+        .rule_weighted("VP", &["V", "NP", "PP"], 0.3)
+        .rule_weighted("VP", &["V", "NP"], 0.7)
+        .rule_weighted("NP", &["NP", "PP"], 0.2)
+        .rule_weighted("NP", &["D", "N"], 0.8)
+        .rule("PP", &["P", "NP"])
+        .into_grammar("VP")
+        .expect("Bad grammar");
+    let mut ev = EarleyForest::new(|_, w| w.to_string());
+    ev.default_action(|n| format!("({})", n.join(" ")));
+    let input = "saw the man with the telescope";
+    let trees = EarleyParser::new(grammar)
+        .parse(input.split_whitespace())
+        .unwrap();
+    let best = "(saw (the man) (with (the telescope)))";
+    assert_eq!(ev.eval_best(&trees), Ok(best.to_string()));
+    let k_best = ev.eval_k_best(&trees, 5).unwrap();
+    let (trees, scores): (Vec<_>, Vec<_>) = k_best.into_iter().unzip();
+    assert_eq!(
+        trees,
+        vec![best, "(saw ((the man) (with (the telescope))))"]
+    );
+    assert!((scores[0] - 0.3 * 0.8 * 0.8).abs() < 1e-9);
+    assert!((scores[1] - 0.7 * 0.2 * 0.8 * 0.8).abs() < 1e-9);
+}
+
+#[test]
+fn weighted_best_parse_leo() {
+    // S -> T S is right recursive, its completions go through Leo items and
+    // the Spans they skip are rebuilt every time sources are asked for. Each
+    // group has its own reduction path.
+    let grammar = GrammarBuilder::default()
+        .nonterm("P")
+        .nonterm("Q")
+        .nonterm("S")
+        .nonterm("T")
+        .nonterm("A")
+        .nonterm("B")
+        .terminal("x", |w| w == "x")
+        .terminal("(", |w| w == "(")
+        .terminal(")", |w| w == ")")
+        .rule("P", &["P", "Q"])
+        .rule("P", &["Q"])
+        .rule("Q", &["(", "S", ")"])
+        .rule("S", &["T", "S"])
+        .rule("S", &["T"])
+        .rule("T", &["A"])
+        .rule("T", &["B"])
+        .rule_weighted("A", &["x"], 0.9)
+        .rule_weighted("B", &["x"], 0.4)
+        .into_grammar("P")
+        .expect("Bad grammar");
+    // Trees evaluate to their score
+    let mut ev = EarleyForest::new(|_, _| 1.0);
+    ev.default_action(|n: Vec<f64>| n.iter().product());
+    ev.action("A -> x", |_| 0.9);
+    ev.action("B -> x", |_| 0.4);
+    let parser = EarleyParser::new(grammar);
+    let group = |len| format!("( {}) ", "x ".repeat(len));
+    for sizes in [[1, 2, 2], [2, 3, 1], [3, 3, 3], [4, 1, 3]] {
+        let input: String = sizes.into_iter().map(group).collect();
+        let trees = parser.parse(input.split_whitespace()).unwrap();
+        let mut all = ev.eval_all(&trees).unwrap();
+        all.sort_by(|a, b| b.total_cmp(a));
+        let k_best = ev.eval_k_best(&trees, 3).unwrap();
+        assert_eq!(k_best.len(), 3);
+        for (idx, (tree, score)) in k_best.into_iter().enumerate() {
+            assert!((tree - score).abs() < 1e-9, "{}: {} {}", input, tree, score);
+            assert!((score - all[idx]).abs() < 1e-9, "{}: {:?}", input, all);
+        }
+        assert!((ev.eval_best(&trees).unwrap() - all[0]).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn weighted_best_parse_cycles() {
+    // X and Y derive each other, finite trees don't repeat a rule on a range
+    let grammar = GrammarBuilder::default()
+        .nonterm("R")
+        .nonterm("X")
+        .nonterm("Y")
+        .terminal("a", |w| w == "a")
+        .rule_weighted("R", &["X"], 2.0)
+        .rule("R", &["Y"])
+        .rule_weighted("X", &["Y"], 2.0)
+        .rule("X", &["a"])
+        .rule_weighted("Y", &["X"], 2.0)
+        .rule("Y", &["a"])
+        .into_grammar("R")
+        .expect("Bad grammar");
+    let mut ev = EarleyForest::new(|_, w| w.to_string());
+    ev.default_action(|n| n.concat());
+    ev.action("R -> X", |n| format!("R(X({}))", n[0]));
+    ev.action("R -> Y", |n| format!("R(Y({}))", n[0]));
+    ev.action("X -> Y", |n| format!("Y({})", n[0]));
+    ev.action("Y -> X", |n| format!("X({})", n[0]));
+    let trees = EarleyParser::new(grammar).parse(["a"].into_iter()).unwrap();
+    assert_eq!(ev.eval_best(&trees), Ok("R(X(Y(X(a))))".to_string()));
+    let k_best = ev.eval_k_best(&trees, 10).unwrap();
+    let mut found: Vec<_> = k_best.iter().map(|(t, s)| (t.as_str(), *s)).collect();
+    found.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+    assert_eq!(
+        found,
+        vec![
+            ("R(X(Y(X(a))))", 8.0),
+            ("R(X(Y(a)))", 4.0),
+            ("R(Y(X(Y(a))))", 4.0),
+            ("R(X(a))", 2.0),
+            ("R(Y(X(a)))", 2.0),
+            ("R(Y(a))", 1.0),
+        ]
+    );
+}
+
+#[test]
+fn k_best_repeated_spans() {
+    // Both S are the same empty Span, each tree picks their parses apart
+    let grammar = GrammarBuilder::default()
+        .nonterm("T")
+        .nonterm("S")
+        .nonterm("N")
+        .nonterm("A")
+        .nonterm("B")
+        .rule("T", &["S", "S"])
+        .rule("S", &["N", "N"])
+        .rule("N", &["A"])
+        .rule("N", &["B"])
+        .rule("A", &[])
+        .rule("B", &[])
+        .into_grammar("T")
+        .expect("Bad grammar");
+    let mut ev = EarleyForest::new(|_, w| w.to_string());
+    ev.default_action(|n| n.concat());
+    ev.action("A -> ", |_| "a".to_string());
+    ev.action("B -> ", |_| "b".to_string());
+    ev.action("S -> N N", |n| format!("({})", n.concat()));
+    let trees = EarleyParser::new(grammar)
+        .parse(std::iter::empty::<&str>())
+        .unwrap();
+    let k_best = ev.eval_k_best(&trees, 20).unwrap();
+    let found: std::collections::HashSet<_> = k_best.into_iter().map(|(t, _)| t).collect();
+    assert_eq!(found.len(), 16);
+    assert!(found.contains("(ab)(ba)"));
+}
+
+#[test]
+fn chart_to_dot() {
+    let grammar = GrammarBuilder::default()
+        .nonterm("S")
+        .terminal("n", |n| n.parse::<u32>().is_ok())
+        .terminal("+", |n| n == "+")
+        .rule("S", &["S", "+", "n"])
+        .rule("S", &["n"])
+        .into_grammar("S")
+        .expect("Bad grammar");
+    let parser = EarleyParser::new(grammar);
+    let mut session = parser.session();
+    for tok in ["1", "+", "2"] {
+        session.feed(tok).unwrap();
+    }
+    let trees = session.parse_trees().unwrap();
+    let dot = trees.to_dot();
+    assert!(dot.starts_with("digraph chart {\n"));
+    assert!(dot.contains("  subgraph cluster_3 {\n    label=\"StateSet 3\";\n"));
+    assert!(dot.contains("[label=\"S -> S + n \u{2022} (0, 3)\", peripheries=2];"));
+    assert!(dot.contains("[label=\"S -> S \u{2022} + n (0, 1)\"];"));
+    assert!(dot.contains("[label=\"'+'\"];"));
+    assert!(dot.contains("[style=dashed];"));
+    assert!(dot.ends_with("}\n"));
+    // Only Spans in the parse tree, the chart also has `S -> S • + n (0, 3)`
+    let nodes = |dot: &str| dot.lines().filter(|l| l.contains("[label=\"S")).count();
+    assert_eq!(nodes(&dot), 6);
+    assert_eq!(nodes(&session.to_dot()), 7);
+
+    // Completions skipped by Leo items are drawn as if they had been built
+    let grammar = GrammarBuilder::default()
+        .nonterm("S")
+        .terminal("a", |n| n == "a")
+        .rule("S", &["a", "S"])
+        .rule("S", &["a"])
+        .into_grammar("S")
+        .expect("Bad grammar");
+    let trees = EarleyParser::new(grammar)
+        .parse("a a a".split_whitespace())
+        .unwrap();
+    let dot = trees.to_dot();
+    for span in ["(0, 3)", "(1, 3)"] {
+        let label = format!("[label=\"S -> a S \u{2022} {}\", peripheries=2];", span);
+        assert!(dot.contains(&label));
+    }
+    assert!(dot.contains("[label=\"S -> a \u{2022} (2, 3)\", peripheries=2];"));
+}
+
+#[test]
+fn parse_other_symbols() {
+    // Expr -> Expr + Term | Term ; Term -> Term * n | n
+    let grammar = GrammarBuilder::default()
+        .nonterm("Expr")
+        .nonterm("Term")
+        .terminal("n", |n| n.parse::<u32>().is_ok())
+        .terminal("+", |n| n == "+")
+        .terminal("*", |n| n == "*")
+        .rule("Expr", &["Expr", "+", "Term"])
+        .rule("Expr", &["Term"])
+        .rule("Term", &["Term", "*", "n"])
+        .rule("Term", &["n"])
+        .into_grammar("Expr")
+        .expect("Bad grammar");
+    let mut ev = EarleyForest::new(|_, n| n.parse().unwrap_or(0));
+    ev.action("Expr -> Expr + Term", |n| n[0] + n[2]);
+    ev.action("Expr -> Term", |n| n[0]);
+    ev.action("Term -> Term * n", |n| n[0] * n[2]);
+    ev.action("Term -> n", |n| n[0]);
+    let parser = EarleyParser::new(grammar.clone());
+    let trees = parser.parse_symbol("Term", "2 * 3".split_whitespace());
+    assert_eq!(ev.eval(&trees.unwrap()), Ok(6));
+    let error = parser
+        .parse_symbol("Term", "2 + 3".split_whitespace())
+        .unwrap_err();
+    assert_eq!(error.position, 1);
+    let mut session = parser.session_for("Term");
+    session.feed("4").unwrap();
+    assert!(!session.accepts("+"));
+    assert_eq!(ev.eval(&session.parse_trees().unwrap()), Ok(4));
+    // The start symbol is still the default
+    let trees = parser.parse("2 + 3".split_whitespace());
+    assert_eq!(ev.eval(&trees.unwrap()), Ok(5));
+
+    // Compose: a list of expressions imported under `math`
+    let list = GrammarBuilder::default()
+        .nonterm("List")
+        .terminal(",", |n| n == ",")
+        .import("math", &grammar)
+        .rule("List", &["List", ",", "math.Expr"])
+        .rule("List", &["math.Expr"])
+        .into_grammar("List")
+        .expect("Bad grammar");
+    let mut ev = EarleyForest::new(|_, n| vec![n.parse().unwrap_or(0)]);
+    ev.action("List -> List , math.Expr", |n| {
+        [&n[0][..], &n[2][..]].concat()
+    });
+    ev.action("List -> math.Expr", |mut n| n.remove(0));
+    ev.action("math.Expr -> math.Expr + math.Term", |n| {
+        vec![n[0][0] + n[2][0]]
+    });
+    ev.action("math.Expr -> math.Term", |mut n| n.remove(0));
+    ev.action("math.Term -> math.Term * n", |n| vec![n[0][0] * n[2][0]]);
+    ev.action("math.Term -> n", |mut n| n.remove(0));
+    let trees = EarleyParser::new(list)
+        .parse("1 + 2 , 3 * 4 + 5".split_whitespace())
+        .unwrap();
+    assert_eq!(ev.eval(&trees), Ok(vec![3, 17]));
+}
+
+#[cfg(feature = "sync")]
+#[test]
+fn share_across_threads() {
+    fn send_sync<T: Send + Sync>(_: &T) {}
+    let grammar = GrammarBuilder::default()
+        .nonterm("S")
+        .terminal("n", |n| n.parse::<u32>().is_ok())
+        .terminal("+", |n| n == "+")
+        .rule("S", &["S", "+", "n"])
+        .rule("S", &["n"])
+        .into_grammar("S")
+        .expect("Bad grammar");
+    send_sync(&grammar);
+    let parser = EarleyParser::new(grammar);
+    let mut ev = EarleyForest::new(|_, n| n.parse().unwrap_or(0));
+    ev.action("S -> S + n", |n| n[0] + n[2]);
+    ev.action("S -> n", |n| n[0]);
+    send_sync(&parser);
+    send_sync(&ev);
+    // Each thread builds its own chart out of the shared grammar
+    let sums = std::thread::scope(|scope| {
+        let workers: Vec<_> = ["1 + 2", "3 + 4 + 5", "6"]
+            .into_iter()
+            .map(|input| {
+                let (parser, ev) = (&parser, &ev);
+                scope.spawn(move || ev.eval(&parser.parse(input.split_whitespace()).unwrap()))
+            })
+            .collect();
+        let sums: Vec<_> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+        sums
+    });
+    assert_eq!(sums, vec![Ok(3), Ok(12), Ok(6)]);
+}
+
+mod parse_session {
+    use super::super::grammar::{Grammar, GrammarBuilder};
+    use super::super::parser::EarleyParser;
+    use super::super::trees::EarleyForest;
+
+    fn grammar_sum() -> Grammar {
+        // S -> S + N | N ; N -> [0-9]
+        GrammarBuilder::default()
+            .nonterm("S")
+            .nonterm("N")
+            .terminal("[+]", |n| n == "+")
+            .terminal("[0-9]", |n| "1234567890".contains(n))
+            .rule("S", &["S", "[+]", "N"])
+            .rule("S", &["N"])
+            .rule("N", &["[0-9]"])
+            .into_grammar("S")
+            .expect("Bad grammar")
+    }
+
+    fn sum_evaler<'a>() -> EarleyForest<'a, f64> {
+        let mut ev = EarleyForest::new(|symbol, token| match symbol {
+            "[0-9]" => token.parse().unwrap(),
+            _ => 0.0,
+        });
+        ev.action("S -> S [+] N", |n| n[0] + n[2]);
+        ev.action("S -> N", |n| n[0]);
+        ev.action("N -> [0-9]", |n| n[0]);
+        ev
+    }
+
+    #[test]
+    fn feed_tokens() {
+        let parser = EarleyParser::new(grammar_sum());
+        let mut session = parser.session();
+        assert!(!session.is_complete());
+        assert_eq!(session.expected(), vec!["[0-9]".to_string()]);
+        assert!(session.accepts("1"));
+        assert!(!session.accepts("+"));
+
+        session.feed("1").unwrap();
+        assert!(session.is_complete());
+        assert_eq!(session.expected(), vec!["[+]".to_string()]);
+
+        // Rejected tokens leave the session untouched
+        let error = session.feed("2").unwrap_err();
+        assert_eq!(error.position, 1);
+        assert_eq!(error.lexeme, Some("2".to_string()));
+        assert_eq!(session.position(), 1);
+
+        session.feed("+").unwrap();
+        assert!(!session.is_complete());
+        assert!(session.parse_trees().is_err());
+        session.feed("2").unwrap();
+        assert!(session.is_complete());
+        assert_eq!(session.position(), 3);
+
+        let ev = sum_evaler();
+        assert_eq!(ev.eval(&session.parse_trees().unwrap()), Ok(3.0));
+    }
+
+    #[test]
+    fn fork_session() {
+        let parser = EarleyParser::new(grammar_sum());
+        let ev = sum_evaler();
+
+        let mut session = parser.session();
+        for tok in ["1", "+", "2"] {
+            session.feed(tok).unwrap();
+        }
+        let snapshot = session.clone();
+        let mut fork = session.clone();
+        session.feed("+").unwrap();
+        session.feed("3").unwrap();
+        fork.feed("+").unwrap();
+        fork.feed("7").unwrap();
+        assert_eq!(ev.eval(&session.parse_trees().unwrap()), Ok(6.0));
+        assert_eq!(ev.eval(&fork.parse_trees().unwrap()), Ok(10.0));
+        assert_eq!(ev.eval(&snapshot.parse_trees().unwrap()), Ok(3.0));
+        assert_eq!(snapshot.position(), 3);
+    }
+}
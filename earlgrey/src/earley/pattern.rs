@@ -0,0 +1,443 @@
+#![deny(warnings)]
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A small regular expression used by terminals that match several chars.
+/// Supports literals, `.`, escapes (`\d`, `\w`, `\s` and their negations),
+/// char classes (`[a-z_]`, `[^0-9]`), groups, alternation `|` and the
+/// `*`, `+` and `?` quantifiers. It's compiled into an NFA that's simulated
+/// in lock-step (Pike VM) so matching is linear on the input.
+#[derive(Clone)]
+pub struct Pattern {
+    source: String,
+    program: Vec<Inst>,
+    // Text matched by patterns without classes nor operators (eg: escaped
+    // EBNF literals), they're matched with `starts_with`
+    literal: Option<String>,
+}
+
+// Set of chars matched by a single NFA step
+#[derive(Clone, Debug, PartialEq)]
+struct Class {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl Class {
+    fn single(c: char) -> Class {
+        Class {
+            negated: false,
+            ranges: vec![(c, c)],
+        }
+    }
+
+    fn any() -> Class {
+        Class {
+            negated: true,
+            ranges: Vec::new(),
+        }
+    }
+
+    fn matches(&self, c: char) -> bool {
+        let found = self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+        found != self.negated
+    }
+
+    // Ranges of the chars this class matches, eg: to put `\D` in `[...]`
+    fn positive_ranges(&self) -> Vec<(char, char)> {
+        if !self.negated {
+            return self.ranges.clone();
+        }
+        let mut ranges = self.ranges.clone();
+        ranges.sort();
+        let mut gaps = Vec::new();
+        let mut from = Some('\0');
+        for (lo, hi) in ranges {
+            if let Some(start) = from.filter(|&start| start < lo) {
+                // Chars skip surrogates, the one before `\u{E000}` is `\u{D7FF}`
+                gaps.push((start, char::from_u32(lo as u32 - 1).unwrap_or('\u{D7FF}')));
+            }
+            if from.is_some_and(|start| start <= hi) {
+                from = char::from_u32(hi as u32 + 1).or(match hi {
+                    '\u{D7FF}' => Some('\u{E000}'),
+                    _ => None,
+                });
+            }
+        }
+        if let Some(start) = from {
+            gaps.push((start, char::MAX));
+        }
+        gaps
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Inst {
+    Char(Class),
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+// Parsed regex before it's compiled into `Inst`s
+enum Node {
+    Char(Class),
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    // Sub-expression, minimum repetitions (0 or 1) and if it can repeat
+    Repeat(Box<Node>, usize, bool),
+}
+
+struct RegexParser<'a> {
+    input: Peekable<Chars<'a>>,
+}
+
+impl RegexParser<'_> {
+    fn parse(mut self) -> Result<Node, String> {
+        let node = self.alternation()?;
+        match self.input.next() {
+            None => Ok(node),
+            Some(c) => Err(format!("Unexpected `{}`", c)),
+        }
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut variants = vec![self.concatenation()?];
+        while self.input.next_if_eq(&'|').is_some() {
+            variants.push(self.concatenation()?);
+        }
+        Ok(match variants.len() {
+            1 => variants.pop().unwrap(),
+            _ => Node::Alt(variants),
+        })
+    }
+
+    fn concatenation(&mut self) -> Result<Node, String> {
+        let mut parts = Vec::new();
+        while let Some(&c) = self.input.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            parts.push(match self.input.next_if(|c| "*+?".contains(*c)) {
+                Some('*') => Node::Repeat(Box::new(atom), 0, true),
+                Some('+') => Node::Repeat(Box::new(atom), 1, true),
+                Some(_) => Node::Repeat(Box::new(atom), 0, false),
+                None => atom,
+            });
+        }
+        Ok(Node::Concat(parts))
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.input.next() {
+            Some('(') => {
+                let node = self.alternation()?;
+                match self.input.next() {
+                    Some(')') => Ok(node),
+                    _ => Err("Missing closing `)`".to_string()),
+                }
+            }
+            Some('[') => Ok(Node::Char(self.class()?)),
+            Some('.') => Ok(Node::Char(Class::any())),
+            Some('\\') => Ok(Node::Char(self.escape()?)),
+            Some(c) if "*+?".contains(c) => Err(format!("Nothing to repeat with `{}`", c)),
+            Some(c) => Ok(Node::Char(Class::single(c))),
+            None => Err("Unexpected end of pattern".to_string()),
+        }
+    }
+
+    fn escape(&mut self) -> Result<Class, String> {
+        let class = |negated, ranges: &[(char, char)]| Class {
+            negated,
+            ranges: ranges.to_vec(),
+        };
+        let digit = [('0', '9')];
+        let word = [('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')];
+        let space = [(' ', ' '), ('\t', '\r')];
+        Ok(match self.input.next() {
+            Some('d') => class(false, &digit),
+            Some('D') => class(true, &digit),
+            Some('w') => class(false, &word),
+            Some('W') => class(true, &word),
+            Some('s') => class(false, &space),
+            Some('S') => class(true, &space),
+            Some('n') => Class::single('\n'),
+            Some('t') => Class::single('\t'),
+            Some(c) => Class::single(c),
+            None => return Err("Unfinished escape".to_string()),
+        })
+    }
+
+    // Char class after the opening `[`. A leading `]` is taken literally.
+    fn class(&mut self) -> Result<Class, String> {
+        let negated = self.input.next_if_eq(&'^').is_some();
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let lo = match self.input.next() {
+                Some(']') if !first => break,
+                Some('\\') => {
+                    let escaped = self.escape()?;
+                    let (lo, hi) = escaped.ranges[0];
+                    if escaped.negated || escaped.ranges.len() > 1 || lo != hi {
+                        ranges.extend(escaped.positive_ranges());
+                        first = false;
+                        continue;
+                    }
+                    lo
+                }
+                Some(c) => c,
+                None => return Err("Missing closing `]`".to_string()),
+            };
+            first = false;
+            // A `-` at the end of the class is a literal
+            let is_range = self.input.peek() == Some(&'-')
+                && self.input.clone().nth(1).is_some_and(|c| c != ']');
+            if !is_range {
+                ranges.push((lo, lo));
+                continue;
+            }
+            self.input.next();
+            let hi = match self.input.next() {
+                Some('\\') => self.escape()?.ranges[0].0,
+                Some(c) => c,
+                None => return Err("Missing closing `]`".to_string()),
+            };
+            if hi < lo {
+                return Err(format!("Bad range {}-{}", lo, hi));
+            }
+            ranges.push((lo, hi));
+        }
+        Ok(Class { negated, ranges })
+    }
+}
+
+// Emit instructions for `node` at the end of `program`
+fn compile(node: Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Char(class) => program.push(Inst::Char(class)),
+        Node::Concat(parts) => parts.into_iter().for_each(|n| compile(n, program)),
+        Node::Alt(variants) => {
+            // Split to each variant in turn, jumping to the end once matched
+            let mut jumps = Vec::new();
+            let last = variants.len() - 1;
+            for (idx, variant) in variants.into_iter().enumerate() {
+                if idx == last {
+                    compile(variant, program);
+                    break;
+                }
+                let split = program.len();
+                program.push(Inst::Split(split + 1, 0));
+                compile(variant, program);
+                jumps.push(program.len());
+                program.push(Inst::Jump(0));
+                program[split] = Inst::Split(split + 1, program.len());
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat(node, 1, _) => {
+            // e+ => L1: e ; split L1, L2 ; L2:
+            let start = program.len();
+            compile(*node, program);
+            program.push(Inst::Split(start, program.len() + 1));
+        }
+        Node::Repeat(node, _, many) => {
+            // e? => split L1, L2 ; L1: e ; L2:
+            // e* => L0: split L1, L2 ; L1: e ; jump L0 ; L2:
+            let split = program.len();
+            program.push(Inst::Split(split + 1, 0));
+            compile(*node, program);
+            if many {
+                program.push(Inst::Jump(split));
+            }
+            program[split] = Inst::Split(split + 1, program.len());
+        }
+    }
+}
+
+impl Pattern {
+    pub fn new(source: &str) -> Result<Pattern, String> {
+        let parser = RegexParser {
+            input: source.chars().peekable(),
+        };
+        let node = parser
+            .parse()
+            .map_err(|e| format!("Bad pattern /{}/: {}", source, e))?;
+        let mut program = Vec::new();
+        compile(node, &mut program);
+        let literal = (program.iter())
+            .map(|inst| match inst {
+                Inst::Char(Class {
+                    negated: false,
+                    ranges,
+                }) => match ranges[..] {
+                    [(lo, hi)] if lo == hi => Some(lo),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        program.push(Inst::Match);
+        Ok(Pattern {
+            source: source.to_string(),
+            program,
+            literal,
+        })
+    }
+
+    /// Escape `literal` so it can be used as a pattern matching only itself
+    pub fn escape(literal: &str) -> String {
+        let mut escaped = String::new();
+        for c in literal.chars() {
+            if "\\.[]()|*+?^/".contains(c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    // Add the thread at `pc` following jumps and splits (epsilon moves)
+    fn add_thread(&self, threads: &mut Vec<usize>, seen: &mut [bool], pc: usize) {
+        if seen[pc] {
+            return;
+        }
+        seen[pc] = true;
+        match self.program[pc] {
+            Inst::Jump(to) => self.add_thread(threads, seen, to),
+            Inst::Split(a, b) => {
+                self.add_thread(threads, seen, a);
+                self.add_thread(threads, seen, b);
+            }
+            _ => threads.push(pc),
+        }
+    }
+
+    /// Byte lengths of all prefixes of `input` that the pattern matches,
+    /// in increasing order. An empty match is reported as length 0.
+    pub fn prefix_matches(&self, input: &str) -> Vec<usize> {
+        if let Some(literal) = &self.literal {
+            return match input.starts_with(literal.as_str()) {
+                true => vec![literal.len()],
+                false => Vec::new(),
+            };
+        }
+        let mut matches = Vec::new();
+        let mut seen = vec![false; self.program.len()];
+        let mut threads = Vec::new();
+        self.add_thread(&mut threads, &mut seen, 0);
+        let mut chars = input.char_indices();
+        loop {
+            let offset = chars.clone().next().map_or(input.len(), |(i, _)| i);
+            if threads
+                .iter()
+                .any(|&pc| matches!(self.program[pc], Inst::Match))
+            {
+                matches.push(offset);
+            }
+            let Some((_, c)) = chars.next() else { break };
+            seen.iter_mut().for_each(|s| *s = false);
+            let mut next_threads = Vec::new();
+            for pc in threads {
+                if let Inst::Char(class) = &self.program[pc]
+                    && class.matches(c)
+                {
+                    self.add_thread(&mut next_threads, &mut seen, pc + 1);
+                }
+            }
+            if next_threads.is_empty() {
+                break;
+            }
+            threads = next_threads;
+        }
+        matches
+    }
+
+    /// Check if the pattern matches the whole `input`
+    pub fn is_match(&self, input: &str) -> bool {
+        self.prefix_matches(input).last() == Some(&input.len())
+    }
+}
+
+impl fmt::Debug for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "/{}/", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pattern;
+
+    #[test]
+    fn full_match() {
+        let p = Pattern::new("[0-9]+").unwrap();
+        assert!(p.is_match("0123"));
+        assert!(!p.is_match(""));
+        assert!(!p.is_match("12a"));
+        let p = Pattern::new(r"[a-z_]\w*|\d+(\.\d+)?").unwrap();
+        assert!(p.is_match("foo_1"));
+        assert!(p.is_match("3.14"));
+        assert!(!p.is_match("3."));
+        assert!(!p.is_match("1x"));
+        let p = Pattern::new("[^\"]*").unwrap();
+        assert!(p.is_match(""));
+        assert!(p.is_match("hello world"));
+        assert!(!p.is_match("a\"b"));
+        let p = Pattern::new(r"[\d.-]+").unwrap();
+        assert!(p.is_match("-1.5"));
+        // Negated escapes and classes
+        let p = Pattern::new(r"[\D]+").unwrap();
+        assert!(p.is_match("ab c"));
+        assert!(!p.is_match("a1"));
+        let p = Pattern::new(r"[^\d]+").unwrap();
+        assert!(p.is_match("ab c"));
+        assert!(!p.is_match("a1"));
+        let p = Pattern::new(r"[\W]+").unwrap();
+        assert!(p.is_match(" -ñ"));
+        assert!(!p.is_match("-_"));
+        assert!(!p.is_match("a"));
+        let p = Pattern::new(r"[\S,]+").unwrap();
+        assert!(p.is_match("a,\u{10FFFF}"));
+        assert!(!p.is_match("a b"));
+        assert!(!p.is_match("\t"));
+        let p = Pattern::new("a(b|)*c?").unwrap();
+        assert!(p.is_match("abbb"));
+        assert!(p.is_match("ac"));
+        assert!(!p.is_match("acc"));
+    }
+
+    #[test]
+    fn prefixes() {
+        let p = Pattern::new("a*").unwrap();
+        assert_eq!(p.prefix_matches("aab"), vec![0, 1, 2]);
+        let p = Pattern::new("[ñé]+x").unwrap();
+        assert_eq!(p.prefix_matches("ñéxx"), vec![5]);
+        let p = Pattern::new(&Pattern::escape("(*)")).unwrap();
+        assert_eq!(p.literal.as_deref(), Some("(*)"));
+        assert_eq!(p.prefix_matches("(*))"), vec![3]);
+        assert!(!p.is_match("*"));
+        assert!(!p.is_match("(*)("));
+        assert_eq!(Pattern::new("").unwrap().prefix_matches("a"), vec![0]);
+        assert_eq!(Pattern::new("a|b").unwrap().literal, None);
+    }
+
+    #[test]
+    fn bad_patterns() {
+        assert!(Pattern::new("[a-").is_err());
+        assert!(Pattern::new("(ab").is_err());
+        assert!(Pattern::new("ab)").is_err());
+        assert!(Pattern::new("*a").is_err());
+        assert!(Pattern::new("[z-a]").is_err());
+    }
+}
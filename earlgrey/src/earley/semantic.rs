@@ -0,0 +1,206 @@
+#![deny(warnings)]
+
+use super::grammar::{Assoc, Grammar, GrammarBuilder, rule_name};
+use super::shared::MaybeSendSync;
+use super::trees::EarleyForest;
+
+/// Defines grammar rules together with their semantic actions so the two
+/// can't drift apart. Actions are attached to the rules as they're added,
+/// no rule strings involved. Builds a `Grammar` and the `EarleyForest`
+/// to evaluate its parses.
+pub struct SemanticGrammarBuilder<'a, ASTNode: Clone> {
+    builder: GrammarBuilder,
+    forest: EarleyForest<'a, ASTNode>,
+}
+
+impl<'a, ASTNode: Clone> SemanticGrammarBuilder<'a, ASTNode> {
+    pub fn new(terminal_parser: impl Fn(&str, &str) -> ASTNode + MaybeSendSync + 'a) -> Self {
+        SemanticGrammarBuilder {
+            builder: GrammarBuilder::default(),
+            forest: EarleyForest::new(terminal_parser),
+        }
+    }
+
+    pub fn nonterm(self, name: &str) -> Self {
+        SemanticGrammarBuilder {
+            builder: self.builder.nonterm(name),
+            ..self
+        }
+    }
+
+    pub fn nonterm_try(&mut self, name: &str) {
+        self.builder.nonterm_try(name);
+    }
+
+    pub fn terminal(
+        self,
+        name: &str,
+        pred: impl Fn(&str) -> bool + MaybeSendSync + 'static,
+    ) -> Self {
+        SemanticGrammarBuilder {
+            builder: self.builder.terminal(name, pred),
+            ..self
+        }
+    }
+
+    pub fn pattern(self, name: &str, pattern: &str) -> Self {
+        SemanticGrammarBuilder {
+            builder: self.builder.pattern(name, pattern),
+            ..self
+        }
+    }
+
+    /// Add a rule and the action that evaluates it
+    pub fn rule(
+        mut self,
+        head: &str,
+        spec: &[&str],
+        action: impl Fn(Vec<ASTNode>) -> ASTNode + MaybeSendSync + 'a,
+    ) -> Self {
+        self.forest.action(&rule_name(head, spec), action);
+        SemanticGrammarBuilder {
+            builder: self.builder.rule(head, spec),
+            ..self
+        }
+    }
+
+    /// Add a rule whose action also gets the input range the rule matched
+    pub fn rule_with_span(
+        mut self,
+        head: &str,
+        spec: &[&str],
+        action: impl Fn(Vec<ASTNode>, usize, usize) -> ASTNode + MaybeSendSync + 'a,
+    ) -> Self {
+        self.forest.action_with_span(&rule_name(head, spec), action);
+        SemanticGrammarBuilder {
+            builder: self.builder.rule(head, spec),
+            ..self
+        }
+    }
+
+    /// Add a rule with precedence and associativity and its action
+    pub fn rule_prec(
+        mut self,
+        head: &str,
+        spec: &[&str],
+        level: u32,
+        assoc: Assoc,
+        action: impl Fn(Vec<ASTNode>) -> ASTNode + MaybeSendSync + 'a,
+    ) -> Self {
+        self.forest.action(&rule_name(head, spec), action);
+        SemanticGrammarBuilder {
+            builder: self.builder.rule_prec(head, spec, level, assoc),
+            ..self
+        }
+    }
+
+    /// Build the `Grammar` and an `EarleyForest` with all its actions
+    pub fn into_grammar(self, start: &str) -> Result<(Grammar, EarleyForest<'a, ASTNode>), String> {
+        Ok((self.builder.into_grammar(start)?, self.forest))
+    }
+}
+
+/// Define a grammar and its semantic actions in one go. Non-terminals are
+/// the rule heads. Expands into a `SemanticGrammarBuilder` and returns the
+/// result of `into_grammar`, ie: the `Grammar` and its `EarleyForest`.
+///
+/// ```
+/// let (grammar, forest) = earlgrey::grammar! {
+///     start: "S";
+///     terminal_parser: |_, tok| tok.parse().unwrap_or(0);
+///     terminals {
+///         "n" => |n| n.parse::<u32>().is_ok(),
+///         "+" => |n| n == "+",
+///     }
+///     rules {
+///         "S" -> "S" "+" "n" => |n| n[0] + n[2];
+///         "S" -> "n" => |n| n[0];
+///     }
+/// }
+/// .unwrap();
+/// let trees = earlgrey::EarleyParser::new(grammar)
+///     .parse("1 + 2".split_whitespace())
+///     .unwrap();
+/// assert_eq!(forest.eval(&trees), Ok(3));
+/// ```
+#[macro_export]
+macro_rules! grammar {
+    (
+        start: $start:literal;
+        terminal_parser: $terminal_parser:expr;
+        terminals { $($terminal:literal => $pred:expr),* $(,)? }
+        rules { $($head:literal -> $($symbol:literal)* => $action:expr;)* }
+    ) => {{
+        let mut builder = $crate::SemanticGrammarBuilder::new($terminal_parser);
+        $(builder = builder.terminal($terminal, $pred);)*
+        $(builder.nonterm_try($head);)*
+        $(builder = builder.rule($head, &[$($symbol),*], $action);)*
+        builder.into_grammar($start)
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::grammar::Assoc;
+    use super::super::parser::EarleyParser;
+    use super::SemanticGrammarBuilder;
+
+    #[test]
+    fn builder_attaches_actions() {
+        let (grammar, forest) = SemanticGrammarBuilder::new(|_, tok| tok.parse().unwrap_or(0))
+            .nonterm("E")
+            .terminal("n", |n| n.parse::<i32>().is_ok())
+            .terminal("-", |n| n == "-")
+            .terminal("*", |n| n == "*")
+            .rule_prec("E", &["E", "-", "E"], 1, Assoc::Left, |n| n[0] - n[2])
+            .rule_prec("E", &["E", "*", "E"], 2, Assoc::Left, |n| n[0] * n[2])
+            .rule("E", &["n"], |n| n[0])
+            .into_grammar("E")
+            .unwrap();
+        assert_eq!(forest.validate(&grammar), Ok(()));
+        let trees = EarleyParser::new(grammar)
+            .parse("8 - 2 * 3 - 1".split_whitespace())
+            .unwrap();
+        assert_eq!(forest.eval_all(&trees), Ok(vec![1]));
+    }
+
+    #[test]
+    fn builder_errors() {
+        let result = SemanticGrammarBuilder::new(|_, _| ())
+            .nonterm("E")
+            .rule("E", &["n"], |_| ())
+            .into_grammar("E");
+        assert_eq!(result.err(), Some("Missing Symbol: n".to_string()));
+    }
+
+    #[test]
+    fn grammar_macro() {
+        let (grammar, forest) = grammar! {
+            start: "list";
+            terminal_parser: |sym, tok| match sym {
+                "item" => vec![tok.to_string()],
+                _ => Vec::new(),
+            };
+            terminals {
+                "item" => |i| i.chars().all(char::is_alphabetic),
+                "," => |c| c == ",",
+            }
+            rules {
+                "list" -> => |_| Vec::new();
+                "list" -> "items" => |mut n| n.remove(0);
+                "items" -> "items" "," "item" => |n| n.concat();
+                "items" -> "item" => |mut n| n.remove(0);
+            }
+        }
+        .unwrap();
+        assert_eq!(forest.validate(&grammar), Ok(()));
+        let parser = EarleyParser::new(grammar);
+        let trees = parser.parse("a , b , c".split_whitespace()).unwrap();
+        assert_eq!(
+            forest.eval(&trees),
+            Ok(vec!["a", "b", "c"].into_iter().map(String::from).collect())
+        );
+        let trees = parser.parse("".split_whitespace()).unwrap();
+        assert_eq!(forest.eval(&trees), Ok(Vec::new()));
+    }
+}
@@ -0,0 +1,270 @@
+#![deny(warnings)]
+
+use super::grammar::{Assoc, Grammar, Rule, Symbol};
+use super::pattern::Pattern;
+use super::shared::Shared;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+// Names the EBNF parser takes as identifiers
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Identifier for non-terminals named otherwise, eg: helpers like `x+`
+fn identifier(name: &str, taken: &HashSet<String>) -> String {
+    let mut id = String::new();
+    for c in name.chars() {
+        let part = match c {
+            '+' => "_plus",
+            '*' => "_star",
+            '?' => "_opt",
+            c if c.is_ascii_alphanumeric() => {
+                id.push(c);
+                continue;
+            }
+            _ => "_",
+        };
+        id.push_str(if id.ends_with('_') { &part[1..] } else { part });
+    }
+    let mut id = id.trim_matches('_').to_string();
+    if !is_identifier(&id) {
+        id.insert_str(0, "n_");
+    }
+    while taken.contains(&id) {
+        id.push('_');
+    }
+    id
+}
+
+// Quote literals with the quote they don't contain. The EBNF tokenizer
+// keeps escapes in literals, so those with both quotes or a backslash are
+// written as a pattern matching the same text instead.
+fn quoted(literal: &str) -> String {
+    let single = literal.contains('\'');
+    if literal.contains('\\') || single && literal.contains('"') {
+        format!("/{}/", Pattern::escape(literal))
+    } else if single {
+        format!("\"{}\"", literal)
+    } else {
+        format!("'{}'", literal)
+    }
+}
+
+// Escape `text` as a JSON string
+pub(crate) fn json_string(text: &str) -> String {
+    let mut json = "\"".to_string();
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn assoc_name(assoc: Assoc) -> &'static str {
+    match assoc {
+        Assoc::Left => "left",
+        Assoc::Right => "right",
+        Assoc::NonAssoc => "nonassoc",
+    }
+}
+
+impl Grammar {
+    // Rules grouped by head. Heads go in the order they're reached from
+    // the start symbol so it doesn't depend on how rules were added.
+    fn rule_groups(&self) -> Vec<(&str, Vec<&Shared<Rule>>)> {
+        let mut groups: Vec<(&str, Vec<_>)> = Vec::new();
+        for rule in self.rules() {
+            match groups.iter_mut().find(|(head, _)| *head == rule.head) {
+                Some((_, rules)) => rules.push(rule),
+                None => groups.push((&rule.head, vec![rule])),
+            }
+        }
+        let mut order = vec![self.start.as_str()];
+        let mut idx = 0;
+        while let Some(head) = order.get(idx) {
+            let rules = groups
+                .iter()
+                .find(|(h, _)| h == head)
+                .map(|(_, rules)| rules);
+            for rule in rules.into_iter().flatten() {
+                for symbol in &rule.spec {
+                    if let Symbol::NonTerm(name) = &**symbol
+                        && !order.contains(&name.as_str())
+                    {
+                        order.push(name);
+                    }
+                }
+            }
+            idx += 1;
+        }
+        // Unreachable heads go last in their original order
+        groups.sort_by_key(|(head, _)| order.iter().position(|h| h == head).unwrap_or(order.len()));
+        groups
+    }
+
+    // How each terminal is written in EBNF. Literals are quoted, patterns
+    // from EBNF keep their name and the rest are plugged by name.
+    fn ebnf_terminal(&self, name: &str) -> String {
+        match self.pattern(name) {
+            Some(p) if p.as_str() == Pattern::escape(name) => quoted(name),
+            Some(p) if name.starts_with('/') || name.starts_with('[') && p.as_str() == name => {
+                name.to_string()
+            }
+            _ if is_identifier(name) => name.to_string(),
+            Some(p) => format!("/{}/", p.as_str()),
+            // Terminals with a predicate and no identifier are taken as literals
+            None => quoted(name),
+        }
+    }
+
+    /// Print the grammar as EBNF that `EbnfGrammarParser` takes back. The
+    /// start symbol must be passed along and terminals that aren't literals
+    /// nor patterns need to be plugged by name. Non-terminals that aren't
+    /// identifiers (eg: helpers like `x+`) are renamed, eg: `x_plus`.
+    pub fn to_ebnf(&self) -> String {
+        let groups = self.rule_groups();
+        let mut taken: HashSet<String> = (self.rules().iter())
+            .flat_map(|rule| {
+                rule.spec
+                    .iter()
+                    .map(|s| s.name())
+                    .chain([rule.head.as_str()])
+            })
+            .filter(|name| is_identifier(name))
+            .map(|name| name.to_string())
+            .collect();
+        let mut names = HashMap::new();
+        for (head, _) in &groups {
+            if !is_identifier(head) {
+                let id = identifier(head, &taken);
+                taken.insert(id.clone());
+                names.insert(*head, id);
+            }
+        }
+        let name = |head: &str| names.get(head).cloned().unwrap_or(head.to_string());
+
+        let mut ebnf = String::new();
+        for (head, rules) in groups {
+            let head = name(head);
+            let alternatives: Vec<_> = rules
+                .iter()
+                .map(|rule| {
+                    let mut alternative: Vec<_> = rule
+                        .spec
+                        .iter()
+                        .map(|symbol| match &**symbol {
+                            Symbol::NonTerm(symbol) => name(symbol),
+                            Symbol::Term(symbol, _) => self.ebnf_terminal(symbol),
+                        })
+                        .collect();
+                    if let Some((level, assoc)) = rule.precedence {
+                        alternative.push(format!("%{} {}", assoc_name(assoc), level));
+                    }
+                    if rule.weight != 1.0 {
+                        alternative.push(format!("%weight {}", rule.weight));
+                    }
+                    alternative.join(" ")
+                })
+                .collect();
+            let indent = " ".repeat(head.len() + 2);
+            write!(ebnf, "{} :=", head).unwrap();
+            for (idx, alternative) in alternatives.iter().enumerate() {
+                if idx > 0 {
+                    write!(ebnf, "\n{}|", indent).unwrap();
+                }
+                // Empty alternatives are left blank, eg: `x := a | ;`
+                if !alternative.is_empty() {
+                    write!(ebnf, " {}", alternative).unwrap();
+                }
+            }
+            writeln!(ebnf, " ;").unwrap();
+        }
+        ebnf
+    }
+
+    /// Serialize the rules as JSON, one rule per line so they're easy to
+    /// diff. Terminals are listed by name along with their pattern if any.
+    pub fn to_json(&self) -> String {
+        let mut terminals = BTreeMap::new();
+        let rules: Vec<_> = self
+            .rules()
+            .iter()
+            .map(|rule| {
+                for symbol in &rule.spec {
+                    if let Symbol::Term(name, _) = &**symbol {
+                        terminals.insert(name.as_str(), self.pattern(name));
+                    }
+                }
+                let spec: Vec<_> = rule.spec.iter().map(|s| json_string(s.name())).collect();
+                let precedence = match rule.precedence {
+                    Some((level, assoc)) => format!("[{}, \"{}\"]", level, assoc_name(assoc)),
+                    None => "null".to_string(),
+                };
+                format!(
+                    "{{\"head\": {}, \"spec\": [{}], \"precedence\": {}, \"weight\": {}}}",
+                    json_string(&rule.head),
+                    spec.join(", "),
+                    precedence,
+                    rule.weight
+                )
+            })
+            .collect();
+        let terminals: Vec<_> = terminals
+            .into_iter()
+            .map(|(name, pattern)| match pattern {
+                Some(p) => format!(
+                    "{{\"name\": {}, \"pattern\": {}}}",
+                    json_string(name),
+                    json_string(p.as_str())
+                ),
+                None => format!("{{\"name\": {}}}", json_string(name)),
+            })
+            .collect();
+        format!(
+            "{{\n  \"start\": {},\n  \"rules\": [\n    {}\n  ],\n  \"terminals\": [\n    {}\n  ]\n}}\n",
+            json_string(&self.start),
+            rules.join(",\n    "),
+            terminals.join(",\n    ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::grammar::{Assoc, GrammarBuilder};
+
+    #[test]
+    fn grammar_json() {
+        let grammar = GrammarBuilder::default()
+            .nonterm("E")
+            .terminal("n", |n| n.parse::<u32>().is_ok())
+            .pattern("+", r"\+")
+            .rule_prec("E", &["E", "+", "E"], 1, Assoc::Left)
+            .rule_weighted("E", &["n"], 0.5)
+            .into_grammar("E")
+            .unwrap();
+        let expected = r#"{
+  "start": "E",
+  "rules": [
+    {"head": "E", "spec": ["E", "+", "E"], "precedence": [1, "left"], "weight": 1},
+    {"head": "E", "spec": ["n"], "precedence": null, "weight": 0.5}
+  ],
+  "terminals": [
+    {"name": "+", "pattern": "\\+"},
+    {"name": "n"}
+  ]
+}
+"#;
+        assert_eq!(grammar.to_json(), expected);
+    }
+}
@@ -0,0 +1,32 @@
+#![deny(warnings)]
+
+// With the `sync` feature a Grammar, its EarleyParser and EarleyForest can
+// be shared across threads: rules and symbols are held by `Arc` and the
+// terminal predicates and semantic actions must be `Send + Sync`. The chart
+// of each parse stays `Rc` based, parse and evaluate on the same thread.
+
+use std::any::Any;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) use std::rc::Rc as Shared;
+#[cfg(feature = "sync")]
+pub(crate) use std::sync::Arc as Shared;
+
+/// Bound on terminal predicates and semantic actions. Only requires
+/// `Send + Sync` when built with the `sync` feature.
+#[cfg(not(feature = "sync"))]
+pub trait MaybeSendSync {}
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> MaybeSendSync for T {}
+
+#[cfg(feature = "sync")]
+pub trait MaybeSendSync: Send + Sync {}
+#[cfg(feature = "sync")]
+impl<T: Send + Sync + ?Sized> MaybeSendSync for T {}
+
+// Matcher of a terminal, returns the value it captures out of the input if
+// the input is accepted. Values needn't be Send, they don't outlive a parse.
+#[cfg(not(feature = "sync"))]
+pub(crate) type Matcher = dyn Fn(&str) -> Option<Box<dyn Any>>;
+#[cfg(feature = "sync")]
+pub(crate) type Matcher = dyn Fn(&str) -> Option<Box<dyn Any>> + Send + Sync;
@@ -1,34 +1,116 @@
 #![deny(warnings)]
 
-use super::grammar::{Rule, Symbol};
+use super::grammar::{Rule, Symbol, SymbolId};
+use super::shared::Shared;
+use std::any::Any;
 use std::rc::Rc;
 use std::{cell, fmt, hash};
 
 #[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub enum SpanSource {
     Completion(Rc<Span>, Rc<Span>),
-    Scan(Rc<Span>, String),
+    // The lexeme and the value its terminal captured out of it
+    Scan(Rc<Span>, String, Capture),
+}
+
+/// Value a terminal captured out of a scanned lexeme. It's left out of
+/// comparisons, scans are told apart by their Span and lexeme.
+#[derive(Clone)]
+pub struct Capture(pub Rc<dyn Any>);
+
+impl PartialEq for Capture {
+    fn eq(&self, _: &Capture) -> bool {
+        true
+    }
+}
+
+impl Eq for Capture {}
+
+impl hash::Hash for Capture {
+    fn hash<H: hash::Hasher>(&self, _: &mut H) {}
+}
+
+impl fmt::Debug for Capture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Capture")
+    }
+}
+
+/// A deterministic reduction path (Leo, 1991). `source` is the only Span in
+/// its StateSet waiting on a symbol, and that symbol is the last of its rule.
+/// Completing the symbol would complete `source` which in turn may be the
+/// only Span waiting on its own rule head (`next`) and so on.
+/// LeoItems are memoized per StateSet/symbol so they compare by identity.
+pub struct LeoItem {
+    pub source: Rc<Span>,
+    pub next: Option<Rc<LeoItem>>,
+    // The Span at the top of the reduction path
+    topmost: Rc<Span>,
+}
+
+impl LeoItem {
+    pub fn new(source: Rc<Span>, next: Option<Rc<LeoItem>>) -> LeoItem {
+        let topmost = next.as_ref().map_or(&source, |n| &n.topmost).clone();
+        LeoItem {
+            source,
+            next,
+            topmost,
+        }
+    }
+
+    pub fn topmost(&self) -> &Rc<Span> {
+        &self.topmost
+    }
+}
+
+impl PartialEq for LeoItem {
+    fn eq(&self, other: &LeoItem) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for LeoItem {}
+
+impl hash::Hash for LeoItem {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        std::ptr::hash(self, state);
+    }
+}
+
+impl fmt::Debug for LeoItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Leo({:?} => {:?})", self.source, self.topmost())
+    }
+}
+
+// Backpointers as stored in the chart. Spans completed through a Leo item
+// skip the intermediate completions, these are rebuilt when unwinding.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+enum Backpointer {
+    Source(SpanSource),
+    Leo(Rc<LeoItem>, Rc<Span>),
 }
 
 /// An Span is a partially matched `Rule`. `dot` shows the match progress.
 pub struct Span {
-    pub rule: Rc<Rule>, // LR0item (dotted rule)
-    pub dot: usize,     // dot position within the rule
-    pub start: usize,   // input stream position where item starts
-    pub end: usize,     // input stream position where item ends
+    pub rule: Shared<Rule>, // LR0item (dotted rule)
+    pub dot: usize,         // dot position within the rule
+    pub start: usize,       // input stream position where item starts
+    pub end: usize,         // input stream position where item ends
 
     // Need a RefCell to update existing Spans. A replacement with the union
     // of backpointers would invalidate other Spans already pointing to this one.
     // Those invalidated items wouldn't have the whole back-pointer list.
     /// backpointers leading to this item: (source-item, Scan/Completion)
-    backpointers: cell::RefCell<Vec<SpanSource>>,
+    backpointers: cell::RefCell<Vec<Backpointer>>,
 }
 
 // Spans are deduped only by rule, dot, start, end (ie: not bp)
 // The intention is that 2 Spans are the same and can be merged ignoring bp.
+// Rules are told apart by their id in the Grammar, not by their symbols.
 impl hash::Hash for Span {
     fn hash<H: hash::Hasher>(&self, state: &mut H) {
-        self.rule.hash(state);
+        self.rule.id.hash(state);
         self.dot.hash(state);
         self.start.hash(state);
         self.end.hash(state);
@@ -37,7 +119,7 @@ impl hash::Hash for Span {
 
 impl PartialEq for Span {
     fn eq(&self, other: &Span) -> bool {
-        self.rule == other.rule
+        self.rule.id == other.rule.id
             && self.dot == other.dot
             && self.start == other.start
             && self.end == other.end
@@ -80,7 +162,7 @@ impl Span {
     fn stringify_bp(&self, nest: usize) -> String {
         let mut out = String::new();
         let pfx = "   ".repeat(nest);
-        for bp in self.backpointers.borrow().iter() {
+        for bp in self.sources().iter() {
             match bp {
                 SpanSource::Completion(a, b) => {
                     out += format!(
@@ -94,7 +176,7 @@ impl Span {
                     )
                     .as_str();
                 }
-                SpanSource::Scan(a, b) => {
+                SpanSource::Scan(a, b, _) => {
                     out += format!(
                         "\n{}Scan(\n{}   {}, \n{}   {}\n{})",
                         pfx,
@@ -154,10 +236,36 @@ impl Span {
         self.rule.spec.get(self.dot).map(|sym| &**sym)
     }
 
+    /// Interned id of the next symbol, cheaper to match than its name
+    pub(crate) fn next_symbol_id(&self) -> Option<SymbolId> {
+        self.rule.spec_ids.get(self.dot).copied()
+    }
+
     /// Scans or Completions that led to the creation of this Span.
-    /// Only ever borrowed non-mutable ref returned for public consumption
-    pub fn sources(&self) -> cell::Ref<Vec<SpanSource>> {
-        self.backpointers.borrow()
+    /// Completions through a Leo item are expanded into the regular
+    /// Completion they skipped, as if the parser had built the whole chain.
+    pub fn sources(&self) -> Vec<SpanSource> {
+        self.backpointers
+            .borrow()
+            .iter()
+            .map(|bp| match bp {
+                Backpointer::Source(source) => source.clone(),
+                Backpointer::Leo(leo, trigger) => self.unwind_leo(leo, trigger.clone()),
+            })
+            .collect()
+    }
+
+    // Rebuild the completions along the reduction path up to the topmost
+    // Span (self). Returns the Completion that would have produced self.
+    fn unwind_leo(&self, mut leo: &Rc<LeoItem>, mut trigger: Rc<Span>) -> SpanSource {
+        while let Some(next) = &leo.next {
+            trigger = Rc::new(Span::extend(
+                SpanSource::Completion(leo.source.clone(), trigger),
+                self.end,
+            ));
+            leo = next;
+        }
+        SpanSource::Completion(leo.source.clone(), trigger)
     }
 
     /// Merge other Span into this one moving over its backpointers
@@ -174,7 +282,7 @@ impl Span {
         }
     }
 
-    pub fn new(rule: &Rc<Rule>, start: usize) -> Span {
+    pub fn new(rule: &Shared<Rule>, start: usize) -> Span {
         Span {
             rule: rule.clone(),
             dot: 0,
@@ -187,14 +295,39 @@ impl Span {
     pub fn extend(extension: SpanSource, end: usize) -> Span {
         let source = match &extension {
             SpanSource::Completion(span, _) => span,
-            SpanSource::Scan(span, _) => span,
+            SpanSource::Scan(span, _, _) => span,
         };
         Span {
             rule: source.rule.clone(),
             dot: source.dot + 1,
             start: source.start,
             end,
-            backpointers: cell::RefCell::new([extension].into()),
+            backpointers: cell::RefCell::new([Backpointer::Source(extension)].into()),
+        }
+    }
+
+    /// Advance `source` over a nullable symbol. Empty completions of the
+    /// symbol are merged in as backpointers as the parser finds them.
+    pub fn skip_nullable(source: &Rc<Span>) -> Span {
+        Span {
+            rule: source.rule.clone(),
+            dot: source.dot + 1,
+            start: source.start,
+            end: source.end,
+            backpointers: cell::RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Complete the topmost Span of a reduction path directly, `trigger`
+    /// being the Span whose completion starts the path.
+    pub fn leo_complete(leo: Rc<LeoItem>, trigger: Rc<Span>, end: usize) -> Span {
+        let source = leo.topmost().clone();
+        Span {
+            rule: source.rule.clone(),
+            dot: source.dot + 1,
+            start: source.start,
+            end,
+            backpointers: cell::RefCell::new([Backpointer::Leo(leo, trigger)].into()),
         }
     }
 }
@@ -203,24 +336,28 @@ impl Span {
 
 #[cfg(test)]
 mod tests {
+    use super::super::shared::MaybeSendSync;
     use super::*;
     use std::cell::RefCell;
     use std::rc::Rc;
 
-    fn terminal(name: &str, pred: impl Fn(&str) -> bool + 'static) -> Rc<Symbol> {
-        Rc::new(Symbol::Term(name.to_string(), Box::new(pred)))
+    fn terminal(
+        name: &str,
+        pred: impl Fn(&str) -> bool + MaybeSendSync + 'static,
+    ) -> Shared<Symbol> {
+        Shared::new(Symbol::term(name, pred))
     }
 
-    fn nonterm(name: &str) -> Rc<Symbol> {
-        Rc::new(Symbol::NonTerm(name.to_string()))
+    fn nonterm(name: &str) -> Shared<Symbol> {
+        Shared::new(Symbol::NonTerm(name.to_string()))
     }
 
-    fn gen_rule1() -> Rc<Rule> {
+    fn gen_rule1() -> Shared<Rule> {
         fn testfn(o: &str) -> bool {
             o.len() == 1 && "+-".contains(o)
         }
         // S -> S +- d
-        Rc::new(Rule::new(
+        Shared::new(Rule::new(
             "S",
             &[
                 nonterm("S"),
@@ -230,12 +367,12 @@ mod tests {
         ))
     }
 
-    fn gen_rule2() -> Rc<Rule> {
+    fn gen_rule2() -> Shared<Rule> {
         fn testfn(o: &str) -> bool {
             o.len() == 1 && "*/".contains(o)
         }
         // S -> S */ d
-        Rc::new(Rule::new(
+        Shared::new(Rule::new(
             "S",
             &[
                 nonterm("S"),
@@ -245,7 +382,7 @@ mod tests {
         ))
     }
 
-    fn item(rule: Rc<Rule>, dot: usize, start: usize, end: usize) -> Span {
+    fn item(rule: Shared<Rule>, dot: usize, start: usize, end: usize) -> Span {
         Span {
             rule,
             dot,
@@ -292,11 +429,15 @@ mod tests {
         let rule1 = gen_rule1();
         let source = Rc::new(item(rule1.clone(), 1, 0, 1));
         // Scan a '+' token
-        let scan = Span::extend(SpanSource::Scan(source.clone(), "+".to_string()), 2);
+        let capture = Capture(Rc::new(()));
+        let scan = Span::extend(
+            SpanSource::Scan(source.clone(), "+".to_string(), capture.clone()),
+            2,
+        );
         assert_eq!(item(rule1.clone(), 2, 0, 2), scan);
         // Check scan item backpointers
         let scan_src = scan.sources();
-        assert!(scan_src.contains(&SpanSource::Scan(source, "+".to_string())));
+        assert!(scan_src.contains(&SpanSource::Scan(source, "+".to_string(), capture)));
         assert_eq!(scan_src.len(), 1);
     }
 
@@ -325,7 +466,7 @@ mod tests {
         let rule1 = gen_rule1();
         let source = Rc::new(item(rule1.clone(), 0, 0, 0));
         // rule3: S -> d
-        let rule3 = Rc::new(Rule::new(
+        let rule3 = Shared::new(Rule::new(
             "S",
             &[terminal("d", |n| n.chars().all(|c| "123".contains(c)))],
         ));
@@ -335,7 +476,7 @@ mod tests {
         let complete1 = Span::extend(SpanSource::Completion(source.clone(), trigger1.clone()), 1);
         assert_eq!(complete1, item(rule1.clone(), 1, 0, 1));
         // rule4: S -> hex
-        let rule4 = Rc::new(Rule::new("S", &[terminal("hex", |n| n == "0x3")]));
+        let rule4 = Shared::new(Rule::new("S", &[terminal("hex", |n| n == "0x3")]));
         // S -> hex .
         let trigger3 = Rc::new(item(rule4, 1, 0, 1));
         // S -> S . + d
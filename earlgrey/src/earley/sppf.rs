@@ -0,0 +1,598 @@
+#![deny(warnings)]
+
+use super::parser::ParseTrees;
+use super::spans::{Span, SpanSource};
+use super::trees::pruned_sources;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// What an SPPF node stands for. All nodes also carry the input range.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SppfLabel {
+    /// A non-terminal completed over the range (any of its rules)
+    Symbol(String),
+    /// A partially matched rule, shown as a dotted rule
+    Intermediate(String),
+    /// A scanned terminal: (terminal name, lexeme)
+    Terminal(String, String),
+}
+
+/// A way of deriving a node. `rule` is the rule being matched, `left` is
+/// the node for the rule matched up to the last symbol and `right` the node
+/// of the last symbol. Both are `None` for empty rules.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackedNode {
+    pub rule: String,
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SppfNode {
+    pub label: SppfLabel,
+    pub start: usize,
+    pub end: usize,
+    /// Alternative derivations of this node. More than one means ambiguity.
+    pub packed: Vec<PackedNode>,
+}
+
+/// A parse tree built out of the SPPF.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Derivation {
+    /// A scanned terminal: (terminal name, lexeme)
+    Leaf(String, String),
+    /// A completed rule and the derivations of its symbols
+    Node(String, Vec<Derivation>),
+}
+
+/// Shared Packed Parse Forest. Encodes all parse trees sharing common
+/// sub-trees so that highly ambiguous parses take polynomial space.
+/// Nodes are indexed by their position in `nodes`.
+pub struct Sppf {
+    nodes: Vec<SppfNode>,
+    root: usize,
+    // Number of trees under each node and each of its packed nodes, see
+    // `CountKey`. Packed nodes that loop back to an ancestor count 0.
+    counts: HashMap<CountKey, (u128, Vec<u128>)>,
+    // Strongly connected component of nodes that are part of a cycle
+    components: Vec<Option<usize>>,
+    cyclic: bool,
+}
+
+// Trees under a node depend on which of its ancestors they can't loop back
+// to. Only ancestors in the node's component can be reached from it, so the
+// key is the node and those ancestors, sorted. No ancestors if acyclic.
+type CountKey = (usize, Vec<usize>);
+
+// Maps chart Spans to SPPF nodes, complete Spans share a node per range
+#[derive(PartialEq, Eq, Hash)]
+enum NodeKey {
+    Symbol(String, usize, usize),
+    Intermediate(Rc<Span>),
+    Terminal(String, usize, usize),
+}
+
+// Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+#[allow(clippy::mutable_key_type)]
+struct SppfBuilder {
+    // Sources of the chart's Spans, pruned like `EarleyForest` does
+    sources: HashMap<Rc<Span>, Vec<SpanSource>>,
+    nodes: Vec<SppfNode>,
+    index: HashMap<NodeKey, usize>,
+    // Spans already queued to have their sources turned into packed nodes
+    expanded: HashSet<Rc<Span>>,
+    pending: Vec<(Rc<Span>, usize)>,
+}
+
+#[allow(clippy::mutable_key_type)]
+impl SppfBuilder {
+    fn node(&mut self, key: NodeKey, label: SppfLabel, start: usize, end: usize) -> usize {
+        if let Some(id) = self.index.get(&key) {
+            return *id;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(SppfNode {
+            label,
+            start,
+            end,
+            packed: Vec::new(),
+        });
+        self.index.insert(key, id);
+        id
+    }
+
+    // Node for a Span, None for Spans that haven't matched anything yet
+    fn span_node(&mut self, span: &Rc<Span>) -> Option<usize> {
+        if span.dot == 0 && !span.complete() {
+            return None;
+        }
+        let id = if span.complete() {
+            let key = NodeKey::Symbol(span.rule.head.clone(), span.start, span.end);
+            let label = SppfLabel::Symbol(span.rule.head.clone());
+            self.node(key, label, span.start, span.end)
+        } else {
+            let label = SppfLabel::Intermediate(dotted_rule(span));
+            self.node(
+                NodeKey::Intermediate(span.clone()),
+                label,
+                span.start,
+                span.end,
+            )
+        };
+        // Complete Spans of different rules share a symbol node, expand each
+        if self.expanded.insert(span.clone()) {
+            self.pending.push((span.clone(), id));
+        }
+        Some(id)
+    }
+
+    fn add_packed(&mut self, span: &Rc<Span>, id: usize) {
+        let rule = span.rule.to_string();
+        let mut packed = Vec::new();
+        // Roots with no trees left have no sources
+        let sources = self.sources.get(span).cloned().unwrap_or_default();
+        for source in sources {
+            let (left, right) = match source {
+                SpanSource::Completion(source, trigger) => {
+                    (self.span_node(&source), self.span_node(&trigger))
+                }
+                SpanSource::Scan(source, lexeme, _) => {
+                    let symbol = source
+                        .next_symbol()
+                        .expect("BUG: missing scan trigger symbol")
+                        .name()
+                        .to_string();
+                    let key = NodeKey::Terminal(symbol.clone(), source.end, span.end);
+                    let label = SppfLabel::Terminal(symbol, lexeme);
+                    let terminal = self.node(key, label, source.end, span.end);
+                    (self.span_node(&source), Some(terminal))
+                }
+            };
+            packed.push(PackedNode {
+                rule: rule.clone(),
+                left,
+                right,
+            });
+        }
+        // Empty rules have no sources but still need a derivation
+        if span.dot == 0 {
+            packed.push(PackedNode {
+                rule,
+                left: None,
+                right: None,
+            });
+        }
+        let node = &mut self.nodes[id];
+        for p in packed {
+            if !node.packed.contains(&p) {
+                node.packed.push(p);
+            }
+        }
+    }
+}
+
+fn dotted_rule(span: &Span) -> String {
+    let names: Vec<_> = span.rule.spec.iter().map(|s| s.name()).collect();
+    format!(
+        "{} -> {} \u{00b7} {}",
+        span.rule.head,
+        names[..span.dot].join(" "),
+        names[span.dot..].join(" ")
+    )
+}
+
+impl Sppf {
+    pub fn new(ptrees: &ParseTrees) -> Sppf {
+        let mut builder = SppfBuilder {
+            sources: pruned_sources(&ptrees.0),
+            nodes: Vec::new(),
+            index: HashMap::new(),
+            expanded: HashSet::new(),
+            pending: Vec::new(),
+        };
+        let mut root = None;
+        for span in &ptrees.0 {
+            root = builder.span_node(span);
+        }
+        while let Some((span, id)) = builder.pending.pop() {
+            builder.add_packed(&span, id);
+        }
+        let mut sppf = Sppf {
+            nodes: builder.nodes,
+            root: root.expect("BUG: ParseTrees empty"),
+            counts: HashMap::new(),
+            components: Vec::new(),
+            cyclic: false,
+        };
+        sppf.components = sppf.components();
+        sppf.count();
+        sppf
+    }
+
+    fn children(&self, id: usize) -> impl Iterator<Item = usize> + '_ {
+        (self.nodes[id].packed.iter()).flat_map(|p| p.left.into_iter().chain(p.right))
+    }
+
+    // Tarjan's strongly connected components, only for nodes in a cycle
+    fn components(&self) -> Vec<Option<usize>> {
+        let n = self.nodes.len();
+        let mut index: Vec<Option<usize>> = vec![None; n];
+        let mut low = vec![0; n];
+        let mut on_stack = vec![false; n];
+        let mut stack = Vec::new();
+        let mut components = vec![None; n];
+        let (mut next_index, mut next_component) = (0, 0);
+        for root in 0..n {
+            if index[root].is_some() {
+                continue;
+            }
+            // Stack of (node, index of the next child to visit)
+            let mut work = vec![(root, 0)];
+            while let Some((id, next)) = work.pop() {
+                if next == 0 {
+                    index[id] = Some(next_index);
+                    low[id] = next_index;
+                    next_index += 1;
+                    stack.push(id);
+                    on_stack[id] = true;
+                }
+                if let Some(child) = self.children(id).nth(next) {
+                    work.push((id, next + 1));
+                    match index[child] {
+                        None => work.push((child, 0)),
+                        Some(child_index) if on_stack[child] => low[id] = low[id].min(child_index),
+                        Some(_) => (),
+                    }
+                    continue;
+                }
+                if let Some(&(parent, _)) = work.last() {
+                    low[parent] = low[parent].min(low[id]);
+                }
+                if Some(low[id]) == index[id] {
+                    let mut component = Vec::new();
+                    while let Some(member) = stack.pop() {
+                        on_stack[member] = false;
+                        component.push(member);
+                        if member == id {
+                            break;
+                        }
+                    }
+                    if component.len() > 1 || self.children(id).any(|c| c == id) {
+                        for member in component {
+                            components[member] = Some(next_component);
+                        }
+                        next_component += 1;
+                    }
+                }
+            }
+        }
+        components
+    }
+
+    fn count_key(&self, id: usize, ancestors: &[usize]) -> CountKey {
+        let Some(component) = self.components[id] else {
+            return (id, Vec::new());
+        };
+        let mut looping: Vec<_> = (ancestors.iter().copied())
+            .filter(|&a| self.components[a] == Some(component))
+            .collect();
+        looping.sort_unstable();
+        (id, looping)
+    }
+
+    // Count trees under each node depth-first. Packed nodes leading back to
+    // an ancestor are cycles (eg: A -> B, B -> A) and are left out,
+    // otherwise there would be infinite trees. Counts of nodes in a cycle
+    // depend on their ancestors, they're kept apart for each set of them.
+    fn count(&mut self) {
+        let mut counts: HashMap<CountKey, (u128, Vec<u128>)> = HashMap::new();
+        // Nodes being counted, ie: ancestors of the node at the top
+        let mut path = Vec::new();
+        let mut on_path = vec![false; self.nodes.len()];
+        // Stack of (node, children were visited)
+        let mut stack = vec![(self.root, false)];
+        while let Some((id, visited)) = stack.pop() {
+            if !visited {
+                if on_path[id] || counts.contains_key(&self.count_key(id, &path)) {
+                    continue;
+                }
+                on_path[id] = true;
+                path.push(id);
+                stack.push((id, true));
+                for child in self.children(id) {
+                    if !on_path[child] && !counts.contains_key(&self.count_key(child, &path)) {
+                        stack.push((child, false));
+                    }
+                }
+                continue;
+            }
+            // Children are all counted, unless they loop back to an ancestor
+            let mut cyclic = false;
+            let mut count_of = |child: Option<usize>| match child {
+                None => 1,
+                Some(c) if on_path[c] => {
+                    cyclic = true;
+                    0
+                }
+                Some(c) => counts[&self.count_key(c, &path)].0,
+            };
+            let packed_counts: Vec<u128> = self.nodes[id]
+                .packed
+                .iter()
+                .map(|p| count_of(p.left).saturating_mul(count_of(p.right)))
+                .collect();
+            self.cyclic |= cyclic;
+            let total = match &self.nodes[id].label {
+                SppfLabel::Terminal(_, _) => 1,
+                _ => packed_counts
+                    .iter()
+                    .fold(0u128, |a, c| a.saturating_add(*c)),
+            };
+            path.pop();
+            on_path[id] = false;
+            counts.insert(self.count_key(id, &path), (total, packed_counts));
+        }
+        self.counts = counts;
+    }
+
+    /// Index of the node deriving the start symbol over the whole input
+    pub fn root(&self) -> usize {
+        self.root
+    }
+
+    pub fn nodes(&self) -> &[SppfNode] {
+        &self.nodes
+    }
+
+    pub fn node(&self, id: usize) -> &SppfNode {
+        &self.nodes[id]
+    }
+
+    /// Number of parse trees in the forest, saturates at `u128::MAX`.
+    /// Derivations that loop (see `is_cyclic`) are not counted.
+    pub fn count_trees(&self) -> u128 {
+        self.counts[&(self.root, Vec::new())].0
+    }
+
+    /// Cyclic grammars (eg: A -> B, B -> A) can derive infinite trees.
+    pub fn is_cyclic(&self) -> bool {
+        self.cyclic
+    }
+
+    /// Lazily iterate over the parse trees, building one at a time.
+    pub fn trees(&self) -> impl Iterator<Item = Derivation> + '_ {
+        (0..self.count_trees()).map(move |idx| self.tree(idx))
+    }
+
+    /// Build the `idx`-th parse tree in the forest.
+    pub fn tree(&self, idx: u128) -> Derivation {
+        assert!(idx < self.count_trees(), "Tree index out of bounds");
+        let mut args = Vec::new();
+        self.unpack(self.root, idx, &mut args, &mut Vec::new());
+        args.pop().expect("BUG: root didn't derive a tree")
+    }
+
+    // Pick a packed node for a tree index (mixed radix on packed counts)
+    // and push the derivations of its symbols into `args`. Counts depend
+    // on the `path` of ancestors as they did when counting.
+    fn unpack(&self, id: usize, mut idx: u128, args: &mut Vec<Derivation>, path: &mut Vec<usize>) {
+        let node = &self.nodes[id];
+        if let SppfLabel::Terminal(name, lexeme) = &node.label {
+            args.push(Derivation::Leaf(name.clone(), lexeme.clone()));
+            return;
+        }
+        let counts = &self.counts[&self.count_key(id, path)].1;
+        path.push(id);
+        let (packed, _) = node
+            .packed
+            .iter()
+            .zip(counts)
+            .find(|(_, count)| {
+                if idx < **count {
+                    return true;
+                }
+                idx -= **count;
+                false
+            })
+            .expect("BUG: tree index out of packed node bounds");
+        let right_count = packed
+            .right
+            .map_or(1, |r| self.counts[&self.count_key(r, path)].0);
+        let mut rule_args = Vec::new();
+        if let Some(left) = packed.left {
+            self.unpack(left, idx / right_count, &mut rule_args, path);
+        }
+        if let Some(right) = packed.right {
+            self.unpack(right, idx % right_count, &mut rule_args, path);
+        }
+        path.pop();
+        match node.label {
+            SppfLabel::Symbol(_) => args.push(Derivation::Node(packed.rule.clone(), rule_args)),
+            // Intermediate nodes just collect args for the rule that completes them
+            _ => args.extend(rule_args),
+        }
+    }
+
+    /// Render the forest in Graphviz DOT format. Ambiguous nodes show their
+    /// alternative derivations as packed (point) nodes.
+    pub fn to_dot(&self) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut out = String::from("digraph sppf {\n");
+        for (id, node) in self.nodes.iter().enumerate() {
+            let (shape, label) = match &node.label {
+                SppfLabel::Symbol(name) => ("ellipse", escape(name)),
+                SppfLabel::Intermediate(rule) => ("box", escape(rule)),
+                SppfLabel::Terminal(name, lexeme) => (
+                    "plaintext",
+                    format!("{} '{}'", escape(name), escape(lexeme)),
+                ),
+            };
+            out += &format!(
+                "  n{} [shape={}, label=\"{}, {}, {}\"];\n",
+                id, shape, label, node.start, node.end
+            );
+            let ambiguous = node.packed.len() > 1;
+            for (pidx, packed) in node.packed.iter().enumerate() {
+                let from = if ambiguous {
+                    out += &format!("  n{}p{} [shape=point];\n", id, pidx);
+                    out += &format!("  n{} -> n{}p{};\n", id, id, pidx);
+                    format!("n{}p{}", id, pidx)
+                } else {
+                    format!("n{}", id)
+                };
+                for child in packed.left.iter().chain(&packed.right) {
+                    out += &format!("  {} -> n{};\n", from, child);
+                }
+            }
+        }
+        out += "}\n";
+        out
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::super::grammar::GrammarBuilder;
+    use super::super::parser::EarleyParser;
+    use super::{Derivation, Sppf, SppfLabel};
+
+    fn catalan_grammar() -> EarleyParser {
+        // S -> S S | b
+        let grammar = GrammarBuilder::default()
+            .nonterm("S")
+            .terminal("b", |n| n == "b")
+            .rule("S", &["S", "S"])
+            .rule("S", &["b"])
+            .into_grammar("S")
+            .expect("Bad grammar");
+        EarleyParser::new(grammar)
+    }
+
+    #[test]
+    fn sppf_shares_nodes() {
+        let parser = catalan_grammar();
+        // Number of binary trees over n leaves is the Catalan number C(n-1)
+        for (n, catalan) in [(1, 1), (2, 1), (3, 2), (4, 5), (5, 14), (8, 429)] {
+            let input = vec!["b"; n];
+            let sppf = Sppf::new(&parser.parse(input.iter()).unwrap());
+            assert_eq!(sppf.count_trees(), catalan);
+            assert_eq!(sppf.trees().count() as u128, catalan);
+            assert!(!sppf.is_cyclic());
+        }
+        // Exponentially many trees in polynomial space
+        let input = vec!["b"; 40];
+        let sppf = Sppf::new(&parser.parse(input.iter()).unwrap());
+        assert_eq!(sppf.count_trees(), 680_425_371_729_975_800_390);
+        assert!(sppf.nodes().len() < 40 * 40 * 3);
+        let mut trees = sppf.trees();
+        assert_ne!(trees.next(), trees.next());
+    }
+
+    #[test]
+    fn sppf_derivations() {
+        let parser = catalan_grammar();
+        let sppf = Sppf::new(&parser.parse("b b b".split_whitespace()).unwrap());
+        let root = sppf.node(sppf.root());
+        assert_eq!(root.label, SppfLabel::Symbol("S".to_string()));
+        assert_eq!((root.start, root.end), (0, 3));
+        assert_eq!(root.packed.len(), 2);
+
+        let b = || {
+            Derivation::Node(
+                "S -> b".to_string(),
+                vec![Derivation::Leaf("b".into(), "b".into())],
+            )
+        };
+        let ss = |l, r| Derivation::Node("S -> S S".to_string(), vec![l, r]);
+        let trees: Vec<_> = sppf.trees().collect();
+        assert_eq!(trees.len(), 2);
+        assert!(trees.contains(&ss(ss(b(), b()), b())));
+        assert!(trees.contains(&ss(b(), ss(b(), b()))));
+    }
+
+    #[test]
+    fn sppf_cycles_and_empty_rules() {
+        // E -> A ; A -> E | n | <e>
+        let grammar = GrammarBuilder::default()
+            .nonterm("E")
+            .nonterm("A")
+            .terminal("n", |n| n == "n")
+            .rule("E", &["A"])
+            .rule("A", &["E"])
+            .rule("A", &["n"])
+            .rule("A", &[])
+            .into_grammar("E")
+            .expect("Bad grammar");
+        let parser = EarleyParser::new(grammar);
+        let sppf = Sppf::new(&parser.parse("n".split_whitespace()).unwrap());
+        assert!(sppf.is_cyclic());
+        assert_eq!(sppf.count_trees(), 1);
+        let n = Derivation::Leaf("n".to_string(), "n".to_string());
+        assert_eq!(
+            sppf.tree(0),
+            Derivation::Node(
+                "E -> A".to_string(),
+                vec![Derivation::Node("A -> n".to_string(), vec![n])]
+            )
+        );
+
+        let sppf = Sppf::new(&parser.parse("".split_whitespace()).unwrap());
+        assert_eq!(sppf.count_trees(), 1);
+        assert_eq!(
+            sppf.tree(0),
+            Derivation::Node(
+                "E -> A".to_string(),
+                vec![Derivation::Node("A -> ".to_string(), vec![])]
+            )
+        );
+    }
+
+    #[test]
+    fn sppf_cycles_shared_nodes() {
+        // R -> X | Y ; X -> Y | a ; Y -> X | a
+        let grammar = GrammarBuilder::default()
+            .nonterm("R")
+            .nonterm("X")
+            .nonterm("Y")
+            .terminal("a", |n| n == "a")
+            .rule("R", &["X"])
+            .rule("R", &["Y"])
+            .rule("X", &["Y"])
+            .rule("X", &["a"])
+            .rule("Y", &["X"])
+            .rule("Y", &["a"])
+            .into_grammar("R")
+            .expect("Bad grammar");
+        let parser = EarleyParser::new(grammar);
+        let sppf = Sppf::new(&parser.parse("a".split_whitespace()).unwrap());
+        assert!(sppf.is_cyclic());
+        // X and Y are counted from R and from each other, the trees without
+        // a loop are R(X(a)), R(X(Y(a))), R(Y(a)) and R(Y(X(a)))
+        assert_eq!(sppf.count_trees(), 4);
+        let node = |rule: &str, child| Derivation::Node(rule.to_string(), vec![child]);
+        let a = || Derivation::Leaf("a".to_string(), "a".to_string());
+        let trees: Vec<_> = sppf.trees().collect();
+        for tree in [
+            node("R -> X", node("X -> a", a())),
+            node("R -> X", node("X -> Y", node("Y -> a", a()))),
+            node("R -> Y", node("Y -> a", a())),
+            node("R -> Y", node("Y -> X", node("X -> a", a()))),
+        ] {
+            assert!(trees.contains(&tree), "missing {:?}", tree);
+        }
+    }
+
+    #[test]
+    fn sppf_to_dot() {
+        let parser = catalan_grammar();
+        let sppf = Sppf::new(&parser.parse("b b b".split_whitespace()).unwrap());
+        let dot = sppf.to_dot();
+        assert!(dot.starts_with("digraph sppf {\n"));
+        assert!(dot.contains("[shape=ellipse, label=\"S, 0, 3\"];"));
+        assert!(dot.contains("[shape=plaintext, label=\"b 'b', 2, 3\"];"));
+        assert!(dot.contains("[shape=point];"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
@@ -1,29 +1,180 @@
 #![deny(warnings)]
 
+use super::grammar::{Grammar, Rule, Symbol};
 use super::parser::ParseTrees;
+use super::shared::MaybeSendSync;
 use super::spans::{Span, SpanSource};
-use std::collections::HashMap;
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
+// Actions and terminal parsers also get the input range they matched,
+// terminal parsers the value captured out of the lexeme too
+#[cfg(not(feature = "sync"))]
+type SemAction<'a, ASTNode> = Box<dyn Fn(Vec<ASTNode>, usize, usize) -> ASTNode + 'a>;
+#[cfg(not(feature = "sync"))]
+type TerminalParser<'a, ASTNode> =
+    Box<dyn Fn(&Symbol, &str, &dyn Any, usize, usize) -> ASTNode + 'a>;
+#[cfg(feature = "sync")]
+type SemAction<'a, ASTNode> = Box<dyn Fn(Vec<ASTNode>, usize, usize) -> ASTNode + Send + Sync + 'a>;
+#[cfg(feature = "sync")]
+type TerminalParser<'a, ASTNode> =
+    Box<dyn Fn(&Symbol, &str, &dyn Any, usize, usize) -> ASTNode + Send + Sync + 'a>;
+
 pub struct EarleyForest<'a, ASTNode: Clone> {
     // Semantic actions to apply when a production is completed
-    actions: HashMap<String, Box<dyn Fn(Vec<ASTNode>) -> ASTNode + 'a>>,
+    actions: HashMap<String, SemAction<'a, ASTNode>>,
     // How to lift a 'scanned' terminal into an AST node.
-    terminal_parser: Box<dyn Fn(&str, &str) -> ASTNode + 'a>,
+    terminal_parser: TerminalParser<'a, ASTNode>,
+    // Fallbacks for rules without a semantic action
+    default_action: Option<SemAction<'a, ASTNode>>,
+    passthrough: bool,
 }
 
 impl<'a, ASTNode: Clone> EarleyForest<'a, ASTNode> {
-    pub fn new(terminal_parser: impl Fn(&str, &str) -> ASTNode + 'a) -> Self {
+    pub fn new(terminal_parser: impl Fn(&str, &str) -> ASTNode + MaybeSendSync + 'a) -> Self {
+        Self::new_with_span(move |symbol, lexeme, _, _| terminal_parser(symbol, lexeme))
+    }
+
+    /// Like `new` but the terminal parser also gets the `start` and `end`
+    /// positions of the lexeme (token indexes, or byte offsets for `parse_str`)
+    pub fn new_with_span(
+        terminal_parser: impl Fn(&str, &str, usize, usize) -> ASTNode + MaybeSendSync + 'a,
+    ) -> Self {
+        EarleyForest {
+            actions: HashMap::new(),
+            terminal_parser: Box::new(move |symbol, lexeme, _, start, end| {
+                terminal_parser(symbol.name(), lexeme, start, end)
+            }),
+            default_action: None,
+            passthrough: false,
+        }
+    }
+
+    /// Like `new` but the terminal parser also gets the value the terminal
+    /// captured out of the lexeme, see `GrammarBuilder::terminal_capture`.
+    /// Plain terminals capture `()`. The value is the one captured while
+    /// parsing, captures aren't run again.
+    pub fn new_with_capture(
+        terminal_parser: impl Fn(&str, &str, &dyn Any) -> ASTNode + MaybeSendSync + 'a,
+    ) -> Self {
         EarleyForest {
             actions: HashMap::new(),
-            terminal_parser: Box::new(terminal_parser),
+            terminal_parser: Box::new(move |symbol, lexeme, value, _, _| {
+                terminal_parser(symbol.name(), lexeme, value)
+            }),
+            default_action: None,
+            passthrough: false,
         }
     }
 
     // Register semantic actions to act when rules are matched
-    pub fn action(&mut self, rule: &str, action: impl Fn(Vec<ASTNode>) -> ASTNode + 'a) {
+    pub fn action(
+        &mut self,
+        rule: &str,
+        action: impl Fn(Vec<ASTNode>) -> ASTNode + MaybeSendSync + 'a,
+    ) {
+        self.action_with_span(rule, move |args, _, _| action(args));
+    }
+
+    /// Register a semantic action that also gets the `start` and `end`
+    /// positions of the input matched by the rule, eg: to report errors.
+    pub fn action_with_span(
+        &mut self,
+        rule: &str,
+        action: impl Fn(Vec<ASTNode>, usize, usize) -> ASTNode + MaybeSendSync + 'a,
+    ) {
         self.actions.insert(rule.to_string(), Box::new(action));
     }
+
+    /// Action for rules that don't have one registered
+    pub fn default_action(
+        &mut self,
+        action: impl Fn(Vec<ASTNode>) -> ASTNode + MaybeSendSync + 'a,
+    ) {
+        self.default_action = Some(Box::new(move |args, _, _| action(args)));
+    }
+
+    /// Rules with a single symbol and no action evaluate to their only child.
+    /// Takes priority over the `default_action`.
+    pub fn passthrough(&mut self) {
+        self.passthrough = true;
+    }
+}
+
+impl<ASTNode: Clone> EarleyForest<'_, ASTNode> {
+    fn has_action(&self, rule: &Rule) -> bool {
+        self.default_action.is_some()
+            || self.passthrough && rule.spec.len() == 1
+            || self.actions.contains_key(&rule.to_string())
+    }
+
+    /// Rules of `grammar` without a semantic action (nor a default for them)
+    pub fn missing_actions(&self, grammar: &Grammar) -> Vec<String> {
+        grammar
+            .rules()
+            .iter()
+            .filter(|rule| !self.has_action(rule))
+            .map(|rule| rule.to_string())
+            .collect()
+    }
+
+    /// Registered actions that don't match any rule of `grammar` (eg: typos)
+    pub fn unknown_actions(&self, grammar: &Grammar) -> Vec<String> {
+        let rules: Vec<_> = grammar.rules().iter().map(|r| r.to_string()).collect();
+        let mut unknown: Vec<_> = self
+            .actions
+            .keys()
+            .filter(|action| !rules.contains(action))
+            .cloned()
+            .collect();
+        unknown.sort();
+        unknown
+    }
+
+    /// Check up front that actions match `grammar` instead of finding out
+    /// with a "Missing Action" error when a rule is first evaluated.
+    pub fn validate(&self, grammar: &Grammar) -> Result<(), String> {
+        let errors: Vec<_> = self
+            .missing_actions(grammar)
+            .into_iter()
+            .map(|rule| format!("Missing Action: {}", rule))
+            .chain(
+                self.unknown_actions(grammar)
+                    .into_iter()
+                    .map(|action| format!("Unknown Action: {}", action)),
+            )
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    // Apply the semantic action for `rule`, or a default one if missing
+    fn apply(
+        &self,
+        rule: &Rule,
+        mut args: Vec<ASTNode>,
+        start: usize,
+        end: usize,
+    ) -> Result<ASTNode, String> {
+        let rulename = rule.to_string();
+        if cfg!(feature = "debug") {
+            eprintln!("Reduction: {}", rulename);
+        }
+        if let Some(action) = self.actions.get(&rulename) {
+            return Ok(action(args, start, end));
+        }
+        if self.passthrough && rule.spec.len() == 1 {
+            return Ok(args.swap_remove(0));
+        }
+        match &self.default_action {
+            Some(action) => Ok(action(args, start, end)),
+            None => Err(format!("Missing Action: {}", rulename)),
+        }
+    }
 }
 
 impl<ASTNode: Clone> EarleyForest<'_, ASTNode> {
@@ -32,17 +183,7 @@ impl<ASTNode: Clone> EarleyForest<'_, ASTNode> {
         if !root.complete() {
             return Ok(args);
         }
-        // Lookup semantic action to apply based on rule name
-        let rulename = root.rule.to_string();
-        match self.actions.get(&rulename) {
-            None => Err(format!("Missing Action: {}", rulename)),
-            Some(action) => {
-                if cfg!(feature = "debug") {
-                    eprintln!("Reduction: {}", rulename);
-                }
-                Ok(vec![action(args)])
-            }
-        }
+        Ok(vec![self.apply(&root.rule, args, root.start, root.end)?])
     }
 
     // To write this helper draw a tree of the backpointers and see how they link.
@@ -52,20 +193,28 @@ impl<ASTNode: Clone> EarleyForest<'_, ASTNode> {
     // Recurse both spans transitively until they have no sources to follow.
     // They will return the 'scans' that happened along the way.
     // - If a span originates from a 'scan' then lift the text into an ASTNode.
-    fn walker(&self, root: &Rc<Span>) -> Result<Vec<ASTNode>, String> {
+    // Sources are looked up in the forest pruned by precedence filters.
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+    #[allow(clippy::mutable_key_type)]
+    fn walker(&self, root: &Rc<Span>, sources: &SourceMap) -> Result<Vec<ASTNode>, String> {
         let mut args = Vec::new();
-        match root.sources().iter().next() {
+        match sources[root].first() {
             Some(SpanSource::Completion(source, trigger)) => {
-                args.extend(self.walker(source)?);
-                args.extend(self.walker(trigger)?);
+                args.extend(self.walker(source, sources)?);
+                args.extend(self.walker(trigger, sources)?);
             }
-            Some(SpanSource::Scan(source, trigger)) => {
+            Some(SpanSource::Scan(source, trigger, capture)) => {
                 let symbol = source
                     .next_symbol()
-                    .expect("BUG: missing scan trigger symbol")
-                    .name();
-                args.extend(self.walker(source)?);
-                args.push((self.terminal_parser)(symbol, trigger));
+                    .expect("BUG: missing scan trigger symbol");
+                args.extend(self.walker(source, sources)?);
+                args.push((self.terminal_parser)(
+                    symbol,
+                    trigger,
+                    &*capture.0,
+                    source.end,
+                    root.end,
+                ));
             }
             None => (),
         }
@@ -73,22 +222,28 @@ impl<ASTNode: Clone> EarleyForest<'_, ASTNode> {
     }
 
     // for non-ambiguous grammars this retreieves the only possible parse
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+    #[allow(clippy::mutable_key_type)]
     pub fn eval_recursive(&self, ptrees: &ParseTrees) -> Result<ASTNode, String> {
+        let sources = pruned_sources(&ptrees.0);
         // walker will always return a Vec of size 1 because root.complete
         Ok(self
-            .walker(ptrees.0.first().expect("BUG: ParseTrees empty"))?
+            .walker(first_root(ptrees, &sources)?, &sources)?
             .swap_remove(0))
     }
 
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+    #[allow(clippy::mutable_key_type)]
     fn walker_all(
         &self,
         root: &Rc<Span>,
+        sources: &SourceMap,
         level: u16,
         mut explored: Vec<SpanSource>,
     ) -> Result<Vec<Vec<ASTNode>>, String> {
         assert!(level < 100, "Bottomless grammar, stack blew up");
-        let source = root.sources();
-        if source.len() == 0 {
+        let source = &sources[root];
+        if source.is_empty() {
             return Ok(vec![self.reduce(root, Vec::new())?]);
         }
         let mut trees = Vec::new();
@@ -101,22 +256,29 @@ impl<ASTNode: Clone> EarleyForest<'_, ASTNode> {
             match backpointer {
                 SpanSource::Completion(source, trigger) => {
                     // collect left-side-tree of each node
-                    for args in self.walker_all(source, level + 1, explored.clone())? {
+                    for args in self.walker_all(source, sources, level + 1, explored.clone())? {
                         // collect right-side-tree of each node
-                        for trig in self.walker_all(trigger, level + 1, explored.clone())? {
+                        for trig in
+                            self.walker_all(trigger, sources, level + 1, explored.clone())?
+                        {
                             let mut args = args.clone();
                             args.extend(trig);
                             trees.push(self.reduce(root, args)?);
                         }
                     }
                 }
-                SpanSource::Scan(source, trigger) => {
-                    for mut args in self.walker_all(source, level + 1, explored.clone())? {
+                SpanSource::Scan(source, trigger, capture) => {
+                    for mut args in self.walker_all(source, sources, level + 1, explored.clone())? {
                         let symbol = source
                             .next_symbol()
-                            .expect("BUG: missing scan trigger symbol")
-                            .name();
-                        args.push((self.terminal_parser)(symbol, trigger));
+                            .expect("BUG: missing scan trigger symbol");
+                        args.push((self.terminal_parser)(
+                            symbol,
+                            trigger,
+                            &*capture.0,
+                            source.end,
+                            root.end,
+                        ));
                         trees.push(self.reduce(root, args)?);
                     }
                 }
@@ -126,11 +288,14 @@ impl<ASTNode: Clone> EarleyForest<'_, ASTNode> {
     }
 
     // Retrieves all parse trees
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+    #[allow(clippy::mutable_key_type)]
     pub fn eval_all_recursive(&self, ptrees: &ParseTrees) -> Result<Vec<ASTNode>, String> {
+        let sources = pruned_sources(&ptrees.0);
         let mut trees = Vec::new();
-        for root in &ptrees.0 {
+        for root in ptrees.0.iter().filter(|root| sources.contains_key(*root)) {
             trees.extend(
-                self.walker_all(root, 0, Vec::new())?
+                self.walker_all(root, &sources, 0, Vec::new())?
                     .into_iter()
                     .map(|mut treevec| treevec.swap_remove(0)),
             );
@@ -158,9 +323,11 @@ impl ForestIterator {
         }
     }
 
-    fn advance(&mut self) -> bool {
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+    #[allow(clippy::mutable_key_type)]
+    fn advance(&mut self, sources: &SourceMap) -> bool {
         while let Some((span, idx)) = self.source_idx.pop() {
-            if idx + 1 < span.sources().len() {
+            if idx + 1 < sources[&span].len() {
                 self.source_idx.push((span, idx + 1));
                 return true;
             }
@@ -192,10 +359,15 @@ impl<ASTNode: Clone> EarleyForest<'_, ASTNode> {
                       /   \
                   .[0-9]   "1"
     */
+    // Evaluate the tree under `root` following the source `selector` picks
+    // out of the (pruned) sources of each Span
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+    #[allow(clippy::mutable_key_type)]
     fn eval_one(
         &self,
         root: Rc<Span>,
-        mut selector: impl FnMut(&Rc<Span>) -> usize,
+        sources: &SourceMap,
+        mut selector: impl FnMut(&Rc<Span>, &[SpanSource]) -> SpanSource,
     ) -> Result<ASTNode, String> {
         let mut args = Vec::new();
         let mut completions = Vec::new();
@@ -209,11 +381,10 @@ impl<ASTNode: Clone> EarleyForest<'_, ASTNode> {
 
             // (Reachable) Spans with no sources mean we've unwound to the
             // begining of a production/rule. Apply the rule reducing args.
-            if cursor.sources().len() == 0 {
-                let completed_rule = &completions
-                    .pop()
-                    .expect("BUG: span rule never completed")
-                    .rule;
+            let span_sources = &sources[&cursor];
+            if span_sources.is_empty() {
+                let completed = completions.pop().expect("BUG: span rule never completed");
+                let completed_rule = &completed.rule;
                 assert_eq!(&cursor.rule, completed_rule);
                 // Get input AST nodes for this reduction. Stored reversed.
                 let num_rule_slots = completed_rule.spec.len();
@@ -223,29 +394,28 @@ impl<ASTNode: Clone> EarleyForest<'_, ASTNode> {
                     .rev()
                     .collect();
                 // Apply the reduction.
-                let rulename = completed_rule.to_string();
-                let action = self
-                    .actions
-                    .get(&rulename)
-                    .ok_or(format!("Missing Action: {}", rulename))?;
-                args.push(action(rule_args));
+                args.push(self.apply(completed_rule, rule_args, completed.start, completed.end)?);
             } else {
-                let span_source_idx = selector(&cursor);
                 // Walk the chart following span sources (back-pointers) of the tree.
-                match &cursor.sources()[span_source_idx] {
+                match selector(&cursor, span_sources) {
                     // Completion sources -> Walk the chart.
                     SpanSource::Completion(source, trigger) => {
-                        spans.push(source.clone());
-                        spans.push(trigger.clone());
+                        spans.push(source);
+                        spans.push(trigger);
                     }
                     // Scan sources -> lift scanned tokens into AST nodes.
-                    SpanSource::Scan(source, trigger) => {
+                    SpanSource::Scan(source, trigger, capture) => {
                         let symbol = source
                             .next_symbol()
-                            .expect("BUG: missing scan trigger symbol")
-                            .name();
-                        args.push((self.terminal_parser)(symbol, trigger));
-                        spans.push(source.clone());
+                            .expect("BUG: missing scan trigger symbol");
+                        args.push((self.terminal_parser)(
+                            symbol,
+                            &trigger,
+                            &*capture.0,
+                            source.end,
+                            cursor.end,
+                        ));
+                        spans.push(source);
                     }
                 }
             }
@@ -254,23 +424,447 @@ impl<ASTNode: Clone> EarleyForest<'_, ASTNode> {
         Ok(args.pop().expect("BUG: mismatched reduce args"))
     }
 
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+    #[allow(clippy::mutable_key_type)]
     pub fn eval(&self, ptrees: &ParseTrees) -> Result<ASTNode, String> {
-        let root = ptrees.0.first().expect("BUG: ParseTrees empty").clone();
-        self.eval_one(root, |_| 0)
+        let sources = pruned_sources(&ptrees.0);
+        let root = first_root(ptrees, &sources)?.clone();
+        self.eval_one(root, &sources, |_, sources| sources[0].clone())
     }
 
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+    #[allow(clippy::mutable_key_type)]
     pub fn eval_all(&self, ptrees: &ParseTrees) -> Result<Vec<ASTNode>, String> {
+        let sources = pruned_sources(&ptrees.0);
         let mut results = Vec::new();
-        for root in &ptrees.0 {
+        for root in ptrees.0.iter().filter(|root| sources.contains_key(*root)) {
             let mut fi = ForestIterator {
                 source_idx: Vec::new(),
             };
             let mut iterator_has_more_items = true;
             while iterator_has_more_items {
-                results.push(self.eval_one(root.clone(), |s| fi.source_index(s))?);
-                iterator_has_more_items = fi.advance();
+                let tree = self.eval_one(root.clone(), &sources, |span, sources| {
+                    sources[fi.source_index(span)].clone()
+                })?;
+                results.push(tree);
+                iterator_has_more_items = fi.advance(&sources);
+            }
+        }
+        Ok(results)
+    }
+}
+
+// Source picked for a Span of a tree in the forest, an index in its merged
+// sources. Children are the picks for the Spans that source points to.
+struct Choice {
+    span: Rc<Span>,
+    source: usize,
+    children: Vec<Rc<Choice>>,
+}
+
+// A tree out of the forest, its score and how to find it
+#[derive(Clone)]
+struct Scored {
+    score: f64,
+    choice: Option<Rc<Choice>>,
+}
+
+type ScoredMemo = HashMap<Rc<Span>, Rc<Vec<Scored>>>;
+
+// Sources of each Span in a forest, compared by value
+type SourceMap = HashMap<Rc<Span>, Vec<SpanSource>>;
+
+// Sources of the Spans under `roots` by value. Spans rebuilt out of Leo items
+// aren't shared and each one only knows the completion it was rebuilt for,
+// merged they have every source the Span would have in a chart without Leo.
+// Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+#[allow(clippy::mutable_key_type)]
+fn merged_sources(roots: &[Rc<Span>]) -> SourceMap {
+    let mut merged = SourceMap::new();
+    // Instances walked so far. Holding on to them keeps addresses unique.
+    let mut walked: HashMap<*const Span, Rc<Span>> = HashMap::new();
+    let mut pending = roots.to_vec();
+    while let Some(span) = pending.pop() {
+        if walked.insert(Rc::as_ptr(&span), span.clone()).is_some() {
+            continue;
+        }
+        let sources = merged.entry(span.clone()).or_default();
+        for source in span.sources() {
+            match &source {
+                SpanSource::Completion(source, trigger) => {
+                    pending.push(source.clone());
+                    pending.push(trigger.clone());
+                }
+                SpanSource::Scan(source, _, _) => pending.push(source.clone()),
+            }
+            if !sources.contains(&source) {
+                sources.push(source);
+            }
+        }
+    }
+    merged
+}
+
+// Disambiguation filter: check if the Span a completion advances can't be
+// advanced by its trigger given their rules precedence (see `Rule::rejects`).
+// Empty derivations are exempt.
+fn rejected(backpointer: &SpanSource) -> bool {
+    match backpointer {
+        SpanSource::Completion(source, trigger) => {
+            trigger.start < trigger.end && source.rule.rejects(source.dot, &trigger.rule)
+        }
+        SpanSource::Scan(_, _, _) => false,
+    }
+}
+
+// Spans a source points to
+fn parts(backpointer: &SpanSource) -> impl Iterator<Item = &Rc<Span>> {
+    let (source, trigger) = match backpointer {
+        SpanSource::Completion(source, trigger) => (source, Some(trigger)),
+        SpanSource::Scan(source, _, _) => (source, None),
+    };
+    std::iter::once(source).chain(trigger)
+}
+
+// Merged sources of the Spans under `roots` (see `merged_sources`) pruned of
+// the derivations precedence filters reject. Spans left with no tree (eg:
+// rejected all the way down) are dropped along with the sources using them,
+// so every source kept leads to a tree.
+// Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+#[allow(clippy::mutable_key_type)]
+pub(crate) fn pruned_sources(roots: &[Rc<Span>]) -> SourceMap {
+    let mut sources = merged_sources(roots);
+    // Sources waiting on their Spans to have a tree: (Span, Spans left)
+    let mut waiting = Vec::new();
+    let mut waited_on: HashMap<Rc<Span>, Vec<usize>> = HashMap::new();
+    for (span, span_sources) in sources.iter_mut() {
+        span_sources.retain(|source| !rejected(source));
+        for source in span_sources.iter() {
+            for part in parts(source) {
+                waited_on
+                    .entry(part.clone())
+                    .or_default()
+                    .push(waiting.len());
+            }
+            waiting.push((span.clone(), parts(source).count()));
+        }
+    }
+    // Spans at the start of their rule have a tree, others once the Spans
+    // of any of their sources do
+    let mut pending: Vec<_> = sources.keys().filter(|s| s.dot == 0).cloned().collect();
+    let mut alive = HashSet::new();
+    while let Some(span) = pending.pop() {
+        if !alive.insert(span.clone()) {
+            continue;
+        }
+        for &idx in waited_on.get(&span).into_iter().flatten() {
+            waiting[idx].1 -= 1;
+            if waiting[idx].1 == 0 {
+                pending.push(waiting[idx].0.clone());
+            }
+        }
+    }
+    sources.retain(|span, _| alive.contains(span));
+    for span_sources in sources.values_mut() {
+        span_sources.retain(|source| parts(source).all(|part| alive.contains(part)));
+    }
+    sources
+}
+
+// First root of the parse with trees left once pruned
+// Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+#[allow(clippy::mutable_key_type)]
+fn first_root<'p>(ptrees: &'p ParseTrees, sources: &SourceMap) -> Result<&'p Rc<Span>, String> {
+    (ptrees.0.iter())
+        .find(|root| sources.contains_key(*root))
+        .ok_or_else(|| "Precedence filters rejected every parse tree".to_string())
+}
+
+impl<ASTNode: Clone> EarleyForest<'_, ASTNode> {
+    // Top `k` trees under `span` by score, ie: the product of the weights
+    // of their rules. Spans already on the `path` (cycles on the chart)
+    // yield no trees so all derivations are finite. Also returns the lowest
+    // depth on the `path` such a cycle looped back to, if any. Trees of a
+    // Span in a cycle depend on its ancestors, only others are memoized.
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+    #[allow(clippy::mutable_key_type)]
+    fn k_best(
+        span: &Rc<Span>,
+        k: usize,
+        sources: &SourceMap,
+        memo: &mut ScoredMemo,
+        path: &mut Vec<Rc<Span>>,
+    ) -> (Rc<Vec<Scored>>, usize) {
+        if let Some(trees) = memo.get(span) {
+            return (trees.clone(), usize::MAX);
+        }
+        if let Some(depth) = path.iter().position(|s| s == span) {
+            return (Rc::new(Vec::new()), depth);
+        }
+        let depth = path.len();
+        let mut looped = usize::MAX;
+        // Each rule in a tree is weighted once, where it's completed
+        let weight = if span.complete() {
+            span.rule.weight
+        } else {
+            1.0
+        };
+        let span_sources = &sources[span];
+        let mut trees = Vec::new();
+        if span_sources.is_empty() {
+            trees.push(Scored {
+                score: weight,
+                choice: None,
+            });
+        }
+        path.push(span.clone());
+        for (idx, backpointer) in span_sources.iter().enumerate() {
+            let mut k_best = |span| {
+                let (trees, depth) = Self::k_best(span, k, sources, memo, path);
+                looped = looped.min(depth);
+                trees
+            };
+            let (left, right) = match backpointer {
+                SpanSource::Completion(source, trigger) => (k_best(source), k_best(trigger)),
+                SpanSource::Scan(source, _, _) => (
+                    k_best(source),
+                    Rc::new(vec![Scored {
+                        score: 1.0,
+                        choice: None,
+                    }]),
+                ),
+            };
+            for l in left.iter() {
+                for r in right.iter() {
+                    let children = [&l.choice, &r.choice].into_iter().flatten().cloned();
+                    trees.push(Scored {
+                        score: weight * l.score * r.score,
+                        choice: Some(Rc::new(Choice {
+                            span: span.clone(),
+                            source: idx,
+                            children: children.collect(),
+                        })),
+                    });
+                }
             }
         }
+        path.pop();
+        trees.sort_by(|a, b| b.score.total_cmp(&a.score));
+        trees.truncate(k);
+        let trees = Rc::new(trees);
+        if looped > depth {
+            memo.insert(span.clone(), trees.clone());
+        }
+        (trees, looped)
+    }
+
+    /// Evaluate the `k` highest scoring trees, best first, along with their
+    /// score: the product of the weights of the rules in the tree (see
+    /// `GrammarBuilder::rule_weighted`). Trees that tie keep no set order.
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashMap is sound
+    #[allow(clippy::mutable_key_type)]
+    pub fn eval_k_best(
+        &self,
+        ptrees: &ParseTrees,
+        k: usize,
+    ) -> Result<Vec<(ASTNode, f64)>, String> {
+        let sources = pruned_sources(&ptrees.0);
+        let mut memo = HashMap::new();
+        let mut best = Vec::new();
+        for root in ptrees.0.iter().filter(|root| sources.contains_key(*root)) {
+            let (trees, _) = Self::k_best(root, k, &sources, &mut memo, &mut Vec::new());
+            best.extend(trees.iter().map(|scored| (root, scored.clone())));
+        }
+        best.sort_by(|a, b| b.1.score.total_cmp(&a.1.score));
+        best.truncate(k);
+        let mut results = Vec::new();
+        for (root, scored) in best {
+            // Spans with sources are walked in the order of their choices:
+            // depth first, the trigger of a completion before its source.
+            // A Span may show up more than once, each with its own choice.
+            let mut pending: Vec<_> = scored.choice.into_iter().collect();
+            let tree = self.eval_one(root.clone(), &sources, |span, sources| {
+                let choice = pending.pop().expect("BUG: tree walk out of choices");
+                assert_eq!(&choice.span, span, "BUG: tree walk out of order");
+                pending.extend(choice.children.iter().cloned());
+                sources[choice.source].clone()
+            })?;
+            results.push((tree, scored.score));
+        }
         Ok(results)
     }
+
+    /// Evaluate the highest scoring tree (Viterbi parse), see `eval_k_best`
+    pub fn eval_best(&self, ptrees: &ParseTrees) -> Result<ASTNode, String> {
+        match self.eval_k_best(ptrees, 1)?.pop() {
+            Some((tree, _)) => Ok(tree),
+            None => Err("No finite parse tree".to_string()),
+        }
+    }
+}
+
+// Steps to walk a tree depth first, leaving a Span once its subtree is done
+enum Step {
+    Enter(Rc<Span>),
+    Leave(Rc<Span>),
+}
+
+/// Lazy iterator over the trees of a parse, see `EarleyForest::eval_iter`.
+/// A tree is told apart by the source picked at each Span with several of
+/// them, in the order they're visited. The next tree bumps the last pick
+/// that has sources left, later Spans start over from their first source.
+struct EvalIter<'f, 'a, ASTNode: Clone> {
+    forest: &'f EarleyForest<'a, ASTNode>,
+    sources: SourceMap,
+    roots: std::slice::Iter<'f, Rc<Span>>,
+    // Root being walked and the (source, sources) picked for its last tree
+    root: Option<Rc<Span>>,
+    picks: Vec<(usize, usize)>,
+}
+
+impl<ASTNode: Clone> Iterator for EvalIter<'_, '_, ASTNode> {
+    type Item = Result<ASTNode, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let root = match self.root.clone() {
+                Some(root) => {
+                    while let Some(&(pick, sources)) = self.picks.last() {
+                        if pick + 1 < sources {
+                            break;
+                        }
+                        self.picks.pop();
+                    }
+                    match self.picks.last_mut() {
+                        Some((pick, _)) => *pick += 1,
+                        None => {
+                            self.root = None;
+                            continue;
+                        }
+                    }
+                    root
+                }
+                None => {
+                    let root = self.roots.next()?.clone();
+                    if !self.sources.contains_key(&root) {
+                        continue;
+                    }
+                    self.root = Some(root.clone());
+                    self.picks.clear();
+                    root
+                }
+            };
+            match self.forest.eval_picks(root, &self.sources, &mut self.picks) {
+                Ok(Some(tree)) => return Some(Ok(tree)),
+                // Picks lead to a cycle, move on to the next ones
+                Ok(None) => continue,
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+impl<ASTNode: Clone> EarleyForest<'_, ASTNode> {
+    // Like `eval_one` following `picks`, the sources picked at Spans with
+    // several of them. Picks for Spans visited past the given ones are
+    // appended (first source). Trees where a Span derives itself (cycles)
+    // are infinite, there's None to evaluate.
+    // Spans hash/eq ignoring their RefCell'd backpointers so HashSet is sound
+    #[allow(clippy::mutable_key_type)]
+    fn eval_picks(
+        &self,
+        root: Rc<Span>,
+        sources: &SourceMap,
+        picks: &mut Vec<(usize, usize)>,
+    ) -> Result<Option<ASTNode>, String> {
+        let mut args = Vec::new();
+        let mut completions = Vec::new();
+        // Spans from the root down to the cursor
+        let mut path = HashSet::new();
+        let mut picked = 0;
+        let mut steps = vec![Step::Enter(root)];
+
+        while let Some(step) = steps.pop() {
+            let cursor = match step {
+                Step::Enter(span) => span,
+                Step::Leave(span) => {
+                    path.remove(&span);
+                    continue;
+                }
+            };
+            // Leo items rebuild Spans, so they're compared by value
+            if !path.insert(cursor.clone()) {
+                return Ok(None);
+            }
+            steps.push(Step::Leave(cursor.clone()));
+            if cursor.complete() {
+                completions.push(cursor.clone());
+            }
+            let span_sources = &sources[&cursor];
+            // Unwound to the start of the rule, apply it (see `eval_one`)
+            if span_sources.is_empty() {
+                let completed = completions.pop().expect("BUG: span rule never completed");
+                let num_rule_slots = completed.rule.spec.len();
+                let rule_args = args
+                    .split_off(args.len() - num_rule_slots)
+                    .into_iter()
+                    .rev()
+                    .collect();
+                args.push(self.apply(
+                    &completed.rule,
+                    rule_args,
+                    completed.start,
+                    completed.end,
+                )?);
+                continue;
+            }
+            let pick = if span_sources.len() > 1 {
+                if picked == picks.len() {
+                    picks.push((0, span_sources.len()));
+                }
+                picked += 1;
+                picks[picked - 1].0
+            } else {
+                0
+            };
+            match &span_sources[pick] {
+                SpanSource::Completion(source, trigger) => {
+                    steps.push(Step::Enter(source.clone()));
+                    steps.push(Step::Enter(trigger.clone()));
+                }
+                SpanSource::Scan(source, trigger, capture) => {
+                    let symbol = source
+                        .next_symbol()
+                        .expect("BUG: missing scan trigger symbol");
+                    args.push((self.terminal_parser)(
+                        symbol,
+                        trigger,
+                        &*capture.0,
+                        source.end,
+                        cursor.end,
+                    ));
+                    steps.push(Step::Enter(source.clone()));
+                }
+            }
+        }
+        assert_eq!(args.len(), 1);
+        Ok(args.pop())
+    }
+
+    /// Evaluate the trees of the parse one at a time, as they're requested.
+    /// Unlike `eval_all` no tree is built upfront so ambiguous parses can be
+    /// cut short, eg: `eval_iter(&trees).take(10)`. Cyclic derivations (eg:
+    /// `A -> B ; B -> A | x`) are skipped so it always terminates.
+    pub fn eval_iter<'f>(
+        &'f self,
+        ptrees: &'f ParseTrees,
+    ) -> impl Iterator<Item = Result<ASTNode, String>> + 'f {
+        EvalIter {
+            forest: self,
+            sources: pruned_sources(&ptrees.0),
+            roots: ptrees.0.iter(),
+            root: None,
+            picks: Vec::new(),
+        }
+    }
 }
@@ -1,17 +1,31 @@
 #![deny(warnings)]
 
 use super::ebnf_tokenizer::EbnfTokenizer;
-use crate::earley::{EarleyForest, EarleyParser, Grammar, GrammarBuilder};
-use std::cell::RefCell;
+use crate::earley::{
+    Assoc, EarleyForest, EarleyParser, Grammar, GrammarBuilder, MaybeSendSync, Pattern,
+};
+use std::sync::Mutex;
 
 macro_rules! debug {
     ($($args:tt)*) => (if cfg!(feature="debug") { eprintln!($($args)*); })
 }
 
+// Optional precedence and weight of a rule (eg: %left 10 %weight 0.5)
+#[derive(Clone, Debug, Default)]
+struct Annotation {
+    prec: Option<(u32, Assoc)>,
+    weight: Option<f64>,
+}
+
+// A rule body with its annotation
+type Alternative = (Vec<String>, Annotation);
+
 #[derive(Clone, Debug)]
 enum G {
-    VariantList(Vec<Vec<String>>),
+    VariantList(Vec<Alternative>),
+    Alternative(Alternative),
     Variant(Vec<String>),
+    Annotation(Annotation),
     Atom(String),
     Nop,
 }
@@ -35,6 +49,9 @@ fn ebnf_grammar() -> Grammar {
             })
         })
         .terminal("<Chars>", move |s| s.chars().all(|c| !c.is_control()))
+        .terminal("<Pattern>", move |s| {
+            s.len() > 1 && (s.starts_with('[') || s.starts_with('/'))
+        })
         .terminal("@<Tag>", move |s| {
             s.chars().enumerate().all(|(i, c)| {
                 i == 0 && c == '@'
@@ -52,20 +69,45 @@ fn ebnf_grammar() -> Grammar {
         .terminal(")", |s| s == ")")
         .terminal("|", |s| s == "|")
         .terminal("'", |s| s == "'")
+        .terminal("%<Assoc>", |s| {
+            s == "%left" || s == "%right" || s == "%nonassoc"
+        })
+        .terminal("<Level>", |s| {
+            !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+        })
+        .terminal("%weight", |s| s == "%weight")
+        .terminal("<Weight>", |s| {
+            s.starts_with(|c: char| c.is_ascii_digit()) && s.parse::<f64>().is_ok()
+        })
         .terminal("\"", |s| s == "\"")
+        .terminal("<Repeat>", |s| {
+            s == "*" || s == "+" || s == "?" || s.starts_with('{') && s.len() > 1
+        })
         .nonterm("<RuleList>")
         .nonterm("<Rule>")
         .nonterm("<VariantList>")
+        .nonterm("<Alternative>")
         .nonterm("<Variant>")
         .nonterm("<Atom>")
+        .nonterm("<Annotation>")
         .rule("<RuleList>", &["<RuleList>", "<Rule>"])
         .rule("<RuleList>", &["<Rule>"])
         .rule("<Rule>", &["<Id>", ":=", "<VariantList>", ";"])
-        .rule("<VariantList>", &["<VariantList>", "|", "<Variant>"])
-        .rule("<VariantList>", &["<Variant>"])
+        .rule("<VariantList>", &["<VariantList>", "|", "<Alternative>"])
+        .rule("<VariantList>", &["<Alternative>"])
+        .rule("<Alternative>", &["<Variant>"])
+        .rule("<Alternative>", &["<Variant>", "<Annotation>"])
+        .rule("<Alternative>", &["<Annotation>"])
+        .rule("<Alternative>", &[])
+        .rule("<Annotation>", &["%<Assoc>", "<Level>"])
+        .rule("<Annotation>", &["%weight", "<Weight>"])
+        .rule("<Annotation>", &["<Annotation>", "%<Assoc>", "<Level>"])
+        .rule("<Annotation>", &["<Annotation>", "%weight", "<Weight>"])
         .rule("<Variant>", &["<Variant>", "<Atom>"])
         .rule("<Variant>", &["<Atom>"])
         .rule("<Atom>", &["<Id>"])
+        .rule("<Atom>", &["<Pattern>"])
+        .rule("<Atom>", &["<Atom>", "<Repeat>"])
         .rule("<Atom>", &["'", "<Chars>", "'"])
         .rule("<Atom>", &["\"", "<Chars>", "\""])
         .rule("<Atom>", &["[", "<VariantList>", "]"])
@@ -78,25 +120,45 @@ fn ebnf_grammar() -> Grammar {
         .expect("Bad EBNF Grammar")
 }
 
+// Add `head -> rule` to the user grammar, honoring its annotation
+fn add_rule(gb: &mut GrammarBuilder, head: &str, rule: &[String], annotation: Annotation) {
+    debug!("Adding rule {:?} -> {:?} {:?}", head, rule, annotation);
+    let spec = rule.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+    let weight = annotation.weight.unwrap_or(1.0);
+    gb.add_rule(head, &spec, annotation.prec, weight, true);
+}
+
 fn ebnf_terminal_parser(
-    user_grammar_builder: &RefCell<GrammarBuilder>,
+    user_grammar_builder: &Mutex<GrammarBuilder>,
 ) -> impl Fn(&str, &str) -> G + '_ {
     move |symbol, token| {
         match symbol {
             "<Id>" => {
                 debug!("Adding non-term {:?}", token);
-                user_grammar_builder.borrow_mut().nonterm_try(token);
+                user_grammar_builder.lock().unwrap().nonterm_try(token);
             }
             "@<Tag>" => {
                 debug!("Adding non-term {:?}", token);
-                user_grammar_builder.borrow_mut().nonterm_try(token);
+                user_grammar_builder.lock().unwrap().nonterm_try(token);
             }
             "<Chars>" => {
                 debug!("Adding terminal {:?}", token);
-                let tok = token.to_string();
+                // Literals are patterns too so they can scan raw text
                 user_grammar_builder
-                    .borrow_mut()
-                    .terminal_try(token, move |s| s == tok);
+                    .lock()
+                    .unwrap()
+                    .pattern_try(token, &Pattern::escape(token));
+            }
+            "<Pattern>" => {
+                debug!("Adding pattern terminal {:?}", token);
+                let regex = match token.strip_prefix('/') {
+                    Some(regex) => regex.strip_suffix('/').unwrap_or(regex),
+                    None => token,
+                };
+                user_grammar_builder
+                    .lock()
+                    .unwrap()
+                    .pattern_try(token, regex);
             }
             _ => (),
         }
@@ -104,29 +166,73 @@ fn ebnf_terminal_parser(
     }
 }
 
-fn ebnf_rule_action<'a>(ev: &mut EarleyForest<'a, G>, gb: &'a RefCell<GrammarBuilder>) {
+fn ebnf_rule_action<'a>(ev: &mut EarleyForest<'a, G>, gb: &'a Mutex<GrammarBuilder>) {
     ev.action("<Rule> -> <Id> := <VariantList> ;", move |mut n| {
         let id = pull!(G::Atom, n.remove(0));
         let body = pull!(G::VariantList, n.remove(1));
-        let mut t_gb = gb.borrow_mut();
-        for rule in body {
-            debug!("Adding rule {:?} -> {:?}", id, rule);
-            t_gb.rule_try(&id, &rule.iter().map(|s| s.as_str()).collect::<Vec<&str>>());
+        let mut t_gb = gb.lock().unwrap();
+        for (rule, annotation) in body {
+            add_rule(&mut t_gb, &id, &rule, annotation);
         }
         G::Nop
     });
 }
 
 fn ebnf_variantlist_action(ev: &mut EarleyForest<'_, G>) {
-    ev.action("<VariantList> -> <VariantList> | <Variant>", |mut n| {
+    ev.action("<VariantList> -> <VariantList> | <Alternative>", |mut n| {
         let mut body = pull!(G::VariantList, n.remove(0));
-        body.push(pull!(G::Variant, n.remove(1)));
+        body.push(pull!(G::Alternative, n.remove(1)));
         G::VariantList(body)
     });
-    ev.action("<VariantList> -> <Variant>", |mut n| {
+    ev.action("<VariantList> -> <Alternative>", |mut n| {
+        G::VariantList(vec![pull!(G::Alternative, n.remove(0))])
+    });
+    ev.action("<Alternative> -> <Variant>", |mut n| {
+        G::Alternative((pull!(G::Variant, n.remove(0)), Annotation::default()))
+    });
+    ev.action("<Alternative> -> <Variant> <Annotation>", |mut n| {
         let part = pull!(G::Variant, n.remove(0));
-        G::VariantList(vec![part])
+        G::Alternative((part, pull!(G::Annotation, n.remove(0))))
+    });
+    // Empty alternatives, eg: `x := a | ;`
+    ev.action("<Alternative> -> <Annotation>", |mut n| {
+        G::Alternative((Vec::new(), pull!(G::Annotation, n.remove(0))))
+    });
+    ev.action("<Alternative> -> ", |_| {
+        G::Alternative((Vec::new(), Annotation::default()))
+    });
+    // Annotations add up left to right, later ones win
+    let precedence = |mut annotation: Annotation, assoc: G, level: G| {
+        let assoc = match pull!(G::Atom, assoc).as_str() {
+            "%left" => Assoc::Left,
+            "%right" => Assoc::Right,
+            _ => Assoc::NonAssoc,
+        };
+        let level = pull!(G::Atom, level).parse().expect("Bad precedence level");
+        annotation.prec = Some((level, assoc));
+        G::Annotation(annotation)
+    };
+    let weight = |mut annotation: Annotation, weight: G| {
+        annotation.weight = Some(pull!(G::Atom, weight).parse().expect("Bad weight"));
+        G::Annotation(annotation)
+    };
+    ev.action("<Annotation> -> %<Assoc> <Level>", move |mut n| {
+        precedence(Annotation::default(), n.remove(0), n.remove(0))
     });
+    ev.action(
+        "<Annotation> -> <Annotation> %<Assoc> <Level>",
+        move |mut n| {
+            let annotation = pull!(G::Annotation, n.remove(0));
+            precedence(annotation, n.remove(0), n.remove(0))
+        },
+    );
+    ev.action("<Annotation> -> %weight <Weight>", move |mut n| {
+        weight(Annotation::default(), n.remove(1))
+    });
+    ev.action(
+        "<Annotation> -> <Annotation> %weight <Weight>",
+        move |mut n| weight(pull!(G::Annotation, n.remove(0)), n.remove(1)),
+    );
 }
 
 fn ebnf_variant_action(ev: &mut EarleyForest<'_, G>) {
@@ -140,53 +246,41 @@ fn ebnf_variant_action(ev: &mut EarleyForest<'_, G>) {
     });
 }
 
-fn ebnf_grouping_action<'a>(ev: &mut EarleyForest<'a, G>, gb: &'a RefCell<GrammarBuilder>) {
+fn ebnf_grouping_action<'a>(ev: &mut EarleyForest<'a, G>, gb: &'a Mutex<GrammarBuilder>) {
     ev.action("<Atom> -> ( <VariantList> )", move |mut n| {
-        let aux = gb.borrow().unique_symbol_name();
+        let aux = gb.lock().unwrap().unique_symbol_name();
         debug!("Adding non-term {:?}", aux);
-        let mut t_gb = gb.borrow_mut();
+        let mut t_gb = gb.lock().unwrap();
         t_gb.nonterm_try(&aux);
         let body = pull!(G::VariantList, n.remove(1));
-        for rule in body {
-            debug!("Adding rule {:?} -> {:?}", aux, rule);
-            t_gb.rule_try(
-                &aux,
-                &rule.iter().map(|s| s.as_str()).collect::<Vec<&str>>(),
-            );
+        for (rule, annotation) in body {
+            add_rule(&mut t_gb, &aux, &rule, annotation);
         }
         G::Atom(aux)
     });
     ev.action("<Atom> -> ( <VariantList> ) @<Tag>", move |mut n| {
         let aux = pull!(G::Atom, n.remove(3));
         debug!("Adding non-term {:?}", aux);
-        let mut t_gb = gb.borrow_mut();
+        let mut t_gb = gb.lock().unwrap();
         t_gb.nonterm_try(&aux);
         let body = pull!(G::VariantList, n.remove(1));
-        for rule in body {
-            debug!("Adding rule {:?} -> {:?}", aux, rule);
-            t_gb.rule_try(
-                &aux,
-                &rule.iter().map(|s| s.as_str()).collect::<Vec<&str>>(),
-            );
+        for (rule, annotation) in body {
+            add_rule(&mut t_gb, &aux, &rule, annotation);
         }
         G::Atom(aux)
     });
 }
 
-fn ebnf_optional_action<'a>(ev: &mut EarleyForest<'a, G>, gb: &'a RefCell<GrammarBuilder>) {
+fn ebnf_optional_action<'a>(ev: &mut EarleyForest<'a, G>, gb: &'a Mutex<GrammarBuilder>) {
     ev.action("<Atom> -> [ <VariantList> ]", move |mut n| {
         // <Atom> -> aux ; aux -> <e> | <VariantList> ;
-        let aux = gb.borrow().unique_symbol_name();
+        let aux = gb.lock().unwrap().unique_symbol_name();
         debug!("Adding non-term {:?}", aux);
-        let mut t_gb = gb.borrow_mut();
+        let mut t_gb = gb.lock().unwrap();
         t_gb.nonterm_try(&aux);
         let body = pull!(G::VariantList, n.remove(1));
-        for rule in body {
-            debug!("Adding rule {:?} -> {:?}", aux, rule);
-            t_gb.rule_try(
-                &aux,
-                &rule.iter().map(|s| s.as_str()).collect::<Vec<&str>>(),
-            );
+        for (rule, annotation) in body {
+            add_rule(&mut t_gb, &aux, &rule, annotation);
             debug!("Adding rule {:?} -> []", aux);
             t_gb.rule_try(&aux, &[]);
         }
@@ -195,15 +289,11 @@ fn ebnf_optional_action<'a>(ev: &mut EarleyForest<'a, G>, gb: &'a RefCell<Gramma
     ev.action("<Atom> -> [ <VariantList> ] @<Tag>", move |mut n| {
         let aux = pull!(G::Atom, n.remove(3));
         debug!("Adding non-term {:?}", aux);
-        let mut t_gb = gb.borrow_mut();
+        let mut t_gb = gb.lock().unwrap();
         t_gb.nonterm_try(&aux);
         let body = pull!(G::VariantList, n.remove(1));
-        for rule in body {
-            debug!("Adding rule {:?} -> {:?}", aux, rule);
-            t_gb.rule_try(
-                &aux,
-                &rule.iter().map(|s| s.as_str()).collect::<Vec<&str>>(),
-            );
+        for (rule, annotation) in body {
+            add_rule(&mut t_gb, &aux, &rule, annotation);
             debug!("Adding rule {:?} -> []", aux);
             t_gb.rule_try(&aux, &[]);
         }
@@ -211,21 +301,17 @@ fn ebnf_optional_action<'a>(ev: &mut EarleyForest<'a, G>, gb: &'a RefCell<Gramma
     });
 }
 
-fn ebnf_repeat_action<'a>(ev: &mut EarleyForest<'a, G>, gb: &'a RefCell<GrammarBuilder>) {
+fn ebnf_repeat_action<'a>(ev: &mut EarleyForest<'a, G>, gb: &'a Mutex<GrammarBuilder>) {
     ev.action("<Atom> -> { <VariantList> }", move |mut n| {
         // <Atom> -> aux ; aux -> <e> | <VariantList> aux ;
-        let aux = gb.borrow().unique_symbol_name();
+        let aux = gb.lock().unwrap().unique_symbol_name();
         debug!("Adding non-term {:?}", aux);
-        let mut t_gb = gb.borrow_mut();
+        let mut t_gb = gb.lock().unwrap();
         t_gb.nonterm_try(&aux);
         let body = pull!(G::VariantList, n.remove(1));
-        for mut rule in body {
+        for (mut rule, annotation) in body {
             rule.push(aux.clone());
-            debug!("Adding rule {:?} -> {:?}", aux, rule);
-            t_gb.rule_try(
-                &aux,
-                &rule.iter().map(|s| s.as_str()).collect::<Vec<&str>>(),
-            );
+            add_rule(&mut t_gb, &aux, &rule, annotation);
             debug!("Adding rule {:?} -> []", aux);
             t_gb.rule_try(&aux, &[]);
         }
@@ -235,16 +321,12 @@ fn ebnf_repeat_action<'a>(ev: &mut EarleyForest<'a, G>, gb: &'a RefCell<GrammarB
         // <Atom> -> aux ; aux -> <e> | <VariantList> aux ;
         let aux = pull!(G::Atom, n.remove(3));
         debug!("Adding non-term {:?}", aux);
-        let mut t_gb = gb.borrow_mut();
+        let mut t_gb = gb.lock().unwrap();
         t_gb.nonterm_try(&aux);
         let body = pull!(G::VariantList, n.remove(1));
-        for mut rule in body {
+        for (mut rule, annotation) in body {
             rule.push(aux.clone());
-            debug!("Adding rule {:?} -> {:?}", aux, rule);
-            t_gb.rule_try(
-                &aux,
-                &rule.iter().map(|s| s.as_str()).collect::<Vec<&str>>(),
-            );
+            add_rule(&mut t_gb, &aux, &rule, annotation);
             debug!("Adding rule {:?} -> []", aux);
             t_gb.rule_try(&aux, &[]);
         }
@@ -252,6 +334,61 @@ fn ebnf_repeat_action<'a>(ev: &mut EarleyForest<'a, G>, gb: &'a RefCell<GrammarB
     });
 }
 
+// Min and max (None if unbounded) repetitions for *, +, ?, {m}, {m,} or {m,n}
+fn repeat_bounds(op: &str) -> (usize, Option<usize>) {
+    match op {
+        "*" => (0, None),
+        "+" => (1, None),
+        "?" => (0, Some(1)),
+        count => {
+            let count = &count[1..count.len() - 1];
+            let bound = |n: &str| n.parse().expect("Bad repetition count");
+            match count.split_once(',') {
+                Some((min, "")) => (bound(min), None),
+                Some((min, max)) => (bound(min), Some(bound(max))),
+                None => (bound(count), Some(bound(count))),
+            }
+        }
+    }
+}
+
+fn ebnf_postfix_action<'a>(ev: &mut EarleyForest<'a, G>, gb: &'a Mutex<GrammarBuilder>) {
+    ev.action("<Atom> -> <Atom> <Repeat>", move |mut n| {
+        // Helper is named after the repeated atom and operator (eg: x+)
+        // <Atom> -> aux ; aux -> aux x | x{min} ; when unbounded
+        // <Atom> -> aux ; aux -> x{min} | ... | x{max} ; otherwise
+        let atom = pull!(G::Atom, n.remove(0));
+        let op = pull!(G::Atom, n.remove(0));
+        let aux = format!("{}{}", atom, op);
+        debug!("Adding non-term {:?}", aux);
+        let mut t_gb = gb.lock().unwrap();
+        t_gb.nonterm_try(&aux);
+        let (min, max) = repeat_bounds(&op);
+        match max {
+            None => {
+                add_rule(
+                    &mut t_gb,
+                    &aux,
+                    &[aux.clone(), atom.clone()],
+                    Annotation::default(),
+                );
+                add_rule(&mut t_gb, &aux, &vec![atom; min], Annotation::default());
+            }
+            Some(max) => {
+                for count in min..=max {
+                    add_rule(
+                        &mut t_gb,
+                        &aux,
+                        &vec![atom.clone(); count],
+                        Annotation::default(),
+                    );
+                }
+            }
+        }
+        G::Atom(aux)
+    });
+}
+
 pub struct EbnfGrammarParser {
     start: String,
     grammar: String,
@@ -269,18 +406,35 @@ impl EbnfGrammarParser {
     }
 
     // Plug-in functions that parse Terminals before we build the grammar
-    pub fn plug_terminal(mut self, name: &str, pred: impl Fn(&str) -> bool + 'static) -> Self {
+    pub fn plug_terminal(
+        mut self,
+        name: &str,
+        pred: impl Fn(&str) -> bool + MaybeSendSync + 'static,
+    ) -> Self {
         debug!("Adding terminal {:?}", name);
         self.grammar_builder.terminal_try(name, pred);
         self
     }
 
+    /// Plug-in a terminal that parses its lexemes into a value, see
+    /// `GrammarBuilder::terminal_capture`
+    pub fn plug_terminal_capture<T: 'static>(
+        mut self,
+        name: &str,
+        capture: impl Fn(&str) -> Option<T> + MaybeSendSync + 'static,
+    ) -> Self {
+        debug!("Adding terminal {:?}", name);
+        self.grammar_builder.terminal_capture_try(name, capture);
+        self
+    }
+
     pub fn into_grammar(self) -> Result<Grammar, String> {
-        // Need to move grammar_builder into a refcell because ebnf
+        // Need to move grammar_builder into a mutex because ebnf
         // semantic actions need mutable access to add rules and symbols.
         // These grammar-builder changes are executed while the ebnf-parser
         // is evaluating semantic actions, ie: at `eval_all` line.
-        let grammar_builder = RefCell::new(self.grammar_builder);
+        // (A RefCell would do but actions must be Sync with `sync` feature)
+        let grammar_builder = Mutex::new(self.grammar_builder);
         {
             let mut user_semanter = EarleyForest::new(ebnf_terminal_parser(&grammar_builder));
             user_semanter.action("<RuleList> -> <RuleList> <Rule>", |_| G::Nop);
@@ -291,7 +445,9 @@ impl EbnfGrammarParser {
             ebnf_grouping_action(&mut user_semanter, &grammar_builder);
             ebnf_optional_action(&mut user_semanter, &grammar_builder);
             ebnf_repeat_action(&mut user_semanter, &grammar_builder);
+            ebnf_postfix_action(&mut user_semanter, &grammar_builder);
             user_semanter.action("<Atom> -> <Id>", |mut n| n.remove(0));
+            user_semanter.action("<Atom> -> <Pattern>", |mut n| n.remove(0));
             user_semanter.action("<Atom> -> ' <Chars> '", |mut n| n.remove(1));
             user_semanter.action("<Atom> -> \" <Chars> \"", |mut n| n.remove(1));
 
@@ -303,6 +459,9 @@ impl EbnfGrammarParser {
                 panic!("BUG: EBNF grammar shouldn't be ambiguous!");
             }
         }
-        grammar_builder.into_inner().into_grammar(&self.start)
+        grammar_builder
+            .into_inner()
+            .unwrap()
+            .into_grammar(&self.start)
     }
 }
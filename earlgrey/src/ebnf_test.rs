@@ -22,7 +22,7 @@ where
     let mut tree_builder =
         EarleyForest::new(|sym, tok| Tree::Leaf(sym.to_string(), tok.to_string()));
 
-    for rule in grammar.rules.iter().map(|r| r.to_string()) {
+    for rule in grammar.rules().iter().map(|r| r.to_string()) {
         tree_builder.action(&rule.clone(), move |nodes| Tree::Node(rule.clone(), nodes));
     }
 
@@ -389,3 +389,245 @@ fn plug_terminal() {
         )],
     );
 }
+
+#[test]
+fn plug_terminal_capture() {
+    let g = r#"
+        expr := Number
+              | expr "+" Number ;
+    "#;
+    let grammar = EbnfGrammarParser::new(g, "expr")
+        .plug_terminal_capture("Number", |n| n.parse::<i8>().ok())
+        .into_grammar()
+        .unwrap();
+    let parser = EarleyParser::new(grammar);
+    // Numbers come parsed, other terminals capture ()
+    let mut evaler = EarleyForest::new_with_capture(|symbol, _, value| match symbol {
+        "Number" => *value.downcast_ref::<i8>().unwrap() as i32,
+        _ => {
+            assert!(value.is::<()>());
+            0
+        }
+    });
+    evaler.action("expr -> Number", |n| n[0]);
+    evaler.action("expr -> expr + Number", |n| n[0] + n[2]);
+
+    let trees = parser.parse(["3", "+", "100", "+", "20"].iter()).unwrap();
+    assert_eq!(evaler.eval(&trees), Ok(123));
+    // Lexemes that don't capture a value aren't accepted
+    assert!(parser.parse(["3", "+", "1000"].iter()).is_err());
+}
+
+#[test]
+fn precedence() {
+    let g = r#"
+        expr := expr '+' expr %left 1
+              | expr '*' expr %left 2
+              | expr '^' expr %right 3
+              | n ;
+    "#;
+    let grammar = EbnfGrammarParser::new(g, "expr")
+        .plug_terminal("n", |n| n.chars().all(|c| c.is_ascii_digit()))
+        .into_grammar()
+        .unwrap();
+    let parser = EarleyParser::new(grammar);
+    let mut ev = EarleyForest::new(|_, tok| tok.parse::<u64>().unwrap_or(0));
+    ev.action("expr -> expr + expr", |n| n[0] + n[2]);
+    ev.action("expr -> expr * expr", |n| n[0] * n[2]);
+    ev.action("expr -> expr ^ expr", |n| n[0].pow(n[2] as u32));
+    ev.action("expr -> n", |n| n[0]);
+
+    let eval = |input: &str| ev.eval_all(&parser.parse(input.split_whitespace())?);
+    assert_eq!(eval("1 + 2 * 3 + 4"), Ok(vec![11]));
+    assert_eq!(eval("2 ^ 3 ^ 2"), Ok(vec![512]));
+    assert_eq!(eval("2 * 3 ^ 2"), Ok(vec![18]));
+}
+
+#[test]
+fn weights() {
+    let g = r#"
+        s := s s %weight 0.5
+           | a a %weight 0.4
+           | a %weight 1.5 ;
+    "#;
+    let grammar = EbnfGrammarParser::new(g, "s")
+        .plug_terminal("a", |a| a == "a")
+        .into_grammar()
+        .unwrap();
+    let mut ev = EarleyForest::new(|_, a| a.to_string());
+    ev.default_action(|n| format!("({})", n.join(" ")));
+    let trees = EarleyParser::new(grammar)
+        .parse("a a".split_whitespace())
+        .unwrap();
+    // (a)(a) scores 0.5 * 1.5 * 1.5, more than (a a)
+    let best = ev.eval_k_best(&trees, 2).unwrap();
+    assert_eq!(best[0].0, "((a) (a))");
+    assert_eq!(best[1], ("(a a)".to_string(), 0.4));
+    let annotated = EbnfGrammarParser::new("s := a %left 1 %weight 2 %weight 0.5 ;", "s")
+        .plug_terminal("a", |a| a == "a")
+        .into_grammar()
+        .unwrap();
+    assert_eq!(annotated.rules()[0].weight, 0.5);
+    assert_eq!(annotated.rules()[0].precedence.map(|p| p.0), Some(1));
+}
+
+#[test]
+fn scannerless() {
+    use crate::{Sppf, SppfLabel};
+    let g = r#"
+        list := '[' [ items ] ']' ;
+        items := items ',' item | item ;
+        item := /[a-zñ_]+/ | [0-9]+ ;
+    "#;
+    let grammar = EbnfGrammarParser::new(g, "list").into_grammar().unwrap();
+    let input = "[ab,ñu,42]";
+    let trees = EarleyParser::new(grammar).parse_str(input).unwrap();
+    let sppf = Sppf::new(&trees);
+    assert_eq!(sppf.count_trees(), 1);
+    let mut terminals: Vec<_> = sppf
+        .nodes()
+        .iter()
+        .filter_map(|node| match &node.label {
+            SppfLabel::Terminal(name, lexeme) => Some((node.start, node.end, name, lexeme)),
+            _ => None,
+        })
+        .collect();
+    terminals.sort();
+    for (start, end, _, lexeme) in &terminals {
+        assert_eq!(&input[*start..*end], lexeme.as_str());
+    }
+    let names: Vec<_> = terminals.iter().map(|t| t.2.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["[", "/[a-zñ_]+/", ",", "/[a-zñ_]+/", ",", "[0-9]+", "]"]
+    );
+}
+
+#[test]
+fn nested_optional() {
+    // Not a char class `[[a]`
+    let g = "s := [[a]] b ;";
+    let grammar = EbnfGrammarParser::new(g, "s")
+        .plug_terminal("a", |n| n == "a")
+        .plug_terminal("b", |n| n == "b")
+        .into_grammar()
+        .unwrap();
+    let parser = EarleyParser::new(grammar);
+    assert!(parser.parse("a b".split_whitespace()).is_ok());
+    assert!(parser.parse("b".split_whitespace()).is_ok());
+    assert!(parser.parse("a a b".split_whitespace()).is_err());
+}
+
+#[test]
+fn postfix_repetition() {
+    let g = r#"
+        num := sign? digit+ ;
+        sign := '-' ;
+        pair := 'x'{2} | 'y'{1,2} | ("a" | "b")@ab* | 'z'{2,} ;
+    "#;
+    let grammar = EbnfGrammarParser::new(g, "num")
+        .plug_terminal("digit", |d| {
+            d.len() == 1 && d.chars().all(|c| c.is_ascii_digit())
+        })
+        .into_grammar()
+        .unwrap();
+    let rules: Vec<_> = grammar.rules().iter().map(|r| r.to_string()).collect();
+    for rule in [
+        "sign? -> sign",
+        "sign? -> ",
+        "digit+ -> digit+ digit",
+        "digit+ -> digit",
+        "num -> sign? digit+",
+        "x{2} -> x x",
+        "y{1,2} -> y",
+        "y{1,2} -> y y",
+        "@ab* -> @ab* @ab",
+        "@ab* -> ",
+        "z{2,} -> z{2,} z",
+        "z{2,} -> z z",
+    ] {
+        assert!(rules.contains(&rule.to_string()), "missing {}", rule);
+    }
+    assert!(!rules.contains(&"x{2} -> x".to_string()));
+
+    let parser = ast_parser(grammar).unwrap();
+    let trees = parser(["-", "4", "2"].iter()).unwrap();
+    check_trees(
+        &trees,
+        vec![concat!(
+            r#"Node("num -> sign? digit+", ["#,
+            r#"Node("sign? -> sign", [Node("sign -> -", [Leaf("-", "-")])]), "#,
+            r#"Node("digit+ -> digit+ digit", ["#,
+            r#"Node("digit+ -> digit", [Leaf("digit", "4")]), "#,
+            r#"Leaf("digit", "2")])])"#
+        )],
+    );
+    assert!(parser([].iter()).is_err());
+}
+
+#[test]
+fn print_ebnf_roundtrip() {
+    let g = r#"
+        list := '[' [ items ] ']' ;
+        items := (item ',')* item ;
+        item := /[a-z]+/ | expr | ;
+        expr := expr '+' expr %left 1 %weight 0.5
+              | expr "'" %weight 2
+              | '-'? num ;
+        num := [0-9]+ | digit{2} ;
+    "#;
+    let plugged = |g: &str| {
+        EbnfGrammarParser::new(g, "list")
+            .plug_terminal("digit", |d| {
+                d.len() == 1 && d.chars().all(|c| c.is_ascii_digit())
+            })
+            .into_grammar()
+            .unwrap()
+    };
+    let printed = plugged(g).to_ebnf();
+    assert!(printed.starts_with("list := '[' Uniq_16 ']' ;\nUniq_16 := items\n         | ;\n"));
+    assert!(printed.contains("\nexpr := expr '+' expr %left 1 %weight 0.5\n"));
+    assert!(printed.contains("\ndigit_2 := digit digit ;\n"));
+    let reparsed = plugged(&printed);
+    assert_eq!(reparsed.to_ebnf(), printed);
+    assert_eq!(reparsed.rules().len(), plugged(g).rules().len());
+    let input = "[ab,,-1+22',c]";
+    let count = |g| crate::Sppf::new(&EarleyParser::new(g).parse_str(input).unwrap()).count_trees();
+    assert_eq!(count(reparsed), count(plugged(g)));
+}
+
+#[test]
+fn print_ebnf_quotes() {
+    // Literals EBNF can't quote are printed as patterns matching the same text
+    use crate::{GrammarBuilder, Pattern};
+    let grammar = GrammarBuilder::default()
+        .nonterm("x")
+        .pattern(r#"say "it's""#, &Pattern::escape(r#"say "it's""#))
+        .pattern(r"a\b", &Pattern::escape(r"a\b"))
+        .pattern("'", "'")
+        .rule("x", &[r#"say "it's""#, r"a\b", "'"])
+        .into_grammar("x")
+        .unwrap();
+    let printed = grammar.to_ebnf();
+    assert_eq!(
+        printed,
+        r#"x := /say "it's"/ /a\\b/ "'" ;"#.to_string() + "\n"
+    );
+    let reparsed = EbnfGrammarParser::new(&printed, "x")
+        .into_grammar()
+        .unwrap();
+    assert_eq!(reparsed.to_ebnf(), printed);
+    let input = r#"say "it's"a\b'"#;
+    assert!(EarleyParser::new(grammar).parse_str(input).is_ok());
+    assert!(EarleyParser::new(reparsed).parse_str(input).is_ok());
+    // The empty literal isn't mistaken for an empty pattern, ie: `//`
+    let grammar = EbnfGrammarParser::new("x := 'a' '' ;", "x")
+        .into_grammar()
+        .unwrap();
+    assert_eq!(grammar.to_ebnf(), "x := 'a' '' ;\n");
+    let reparsed = EbnfGrammarParser::new(&grammar.to_ebnf(), "x")
+        .into_grammar()
+        .unwrap();
+    assert_eq!(reparsed.to_ebnf(), grammar.to_ebnf());
+    assert_eq!(reparsed.rules()[0].spec[1].name(), "");
+}
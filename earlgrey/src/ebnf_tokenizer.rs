@@ -3,7 +3,8 @@ pub struct EbnfTokenizer<I: Iterator<Item = char>> {
     buff: Vec<String>,
 }
 
-impl<I: Iterator<Item = char>> EbnfTokenizer<I> {
+// Lookahead to tell char classes apart from optionals needs a Clone input
+impl<I: Iterator<Item = char> + Clone> EbnfTokenizer<I> {
     pub fn new(input: I) -> Self {
         Self {
             input: input.peekable(),
@@ -11,13 +12,94 @@ impl<I: Iterator<Item = char>> EbnfTokenizer<I> {
         }
     }
 
+    // Consume a pattern into `buff` up to the unescaped `close` char
+    fn pattern_until(
+        input: &mut impl Iterator<Item = char>,
+        close: char,
+        buff: &mut String,
+    ) -> Result<(), String> {
+        while let Some(ch) = input.next() {
+            buff.push(ch);
+            if ch == close {
+                return Ok(());
+            }
+            if ch == '\\' {
+                buff.extend(input.next());
+            }
+        }
+        Err(format!("Unfinished pattern missing close {}", close))
+    }
+
+    // Check if the '[' just consumed opens a char class (eg: [0-9], [^"])
+    // rather than an optional (eg: [ x ], ['the'], [a|b]). Char classes
+    // have no whitespace nor quotes and aren't just an identifier. A second
+    // '[' opens a nested optional (eg: [[a]]).
+    fn char_class(&mut self) -> Option<String> {
+        if self.input.peek() == Some(&'[') {
+            return None;
+        }
+        let mut ahead = self.input.clone();
+        let mut class = "[".to_string();
+        class.extend(ahead.next_if_eq(&'^'));
+        // A leading ']' is part of the class
+        class.extend(ahead.next_if_eq(&']'));
+        Self::pattern_until(&mut ahead, ']', &mut class).ok()?;
+        let content = &class[1..class.len() - 1];
+        let identifier = content.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if identifier || content.contains(|c: char| c.is_whitespace() || "'\"|(){}".contains(c)) {
+            return None;
+        }
+        class.extend(ahead.next_if(|c| "*+?".contains(*c)));
+        self.input = ahead;
+        Some(class)
+    }
+
+    // Check if the '{' just consumed opens a repetition count, ie: {m}, {m,}
+    // or {m,n}. Otherwise it's a repeated group (eg: { x }).
+    fn repeat_count(&mut self) -> Option<Result<String, String>> {
+        let mut ahead = self.input.clone();
+        let mut count = "{".to_string();
+        let digits = |ahead: &mut std::iter::Peekable<I>, count: &mut String| {
+            let mut number = String::new();
+            while let Some(digit) = ahead.next_if(|c| c.is_ascii_digit()) {
+                number.push(digit);
+            }
+            count.push_str(&number);
+            number.parse::<usize>().ok()
+        };
+        let min = digits(&mut ahead, &mut count)?;
+        let max = match ahead.next_if_eq(&',') {
+            Some(comma) => {
+                count.push(comma);
+                digits(&mut ahead, &mut count)
+            }
+            None => Some(min),
+        };
+        count.push(ahead.next_if_eq(&'}')?);
+        self.input = ahead;
+        if max.is_some_and(|max| max < min) {
+            return Some(Err(format!("Bad repetition count {}", count)));
+        }
+        Some(Ok(count))
+    }
+
     fn next_result(&mut self) -> Result<Option<String>, String> {
         if !self.buff.is_empty() {
             return Ok(Some(self.buff.remove(0)));
         }
         match self.input.next() {
-            // Various single char tokens.
-            Some(x) if "[]{}()|;".contains(x) => Ok(Some(x.to_string())),
+            // Char classes with an optional quantifier (eg: [0-9]+).
+            Some('[') if let Some(class) = self.char_class() => Ok(Some(class)),
+            // Regex terminals (eg: /[a-z_]+/).
+            Some('/') => {
+                let mut regex = "/".to_string();
+                Self::pattern_until(&mut self.input, '/', &mut regex)?;
+                Ok(Some(regex))
+            }
+            // Repetition counts (eg: {2,4}).
+            Some('{') if let Some(count) = self.repeat_count() => count.map(Some),
+            // Various single char tokens, including repetition operators.
+            Some(x) if "[]{}()|;*+?".contains(x) => Ok(Some(x.to_string())),
             // Assignment operator.
             Some(':') => match self.input.next() {
                 Some('=') => Ok(Some(":=".to_string())),
@@ -68,6 +150,31 @@ impl<I: Iterator<Item = char>> EbnfTokenizer<I> {
                 }
                 Ok(Some(id))
             }
+            // Annotations (eg: %left, %weight).
+            Some('%') => {
+                let mut id = "%".to_string();
+                while let Some(ch) = self.input.peek() {
+                    if !ch.is_ascii_alphabetic() {
+                        break;
+                    }
+                    id.push(self.input.next().unwrap());
+                }
+                Ok(Some(id))
+            }
+            // Precedence levels and weights (eg: 0.25).
+            Some(x) if x.is_ascii_digit() => {
+                let mut number = x.to_string();
+                while let Some(ch) = self.input.next_if(|c| c.is_ascii_digit()) {
+                    number.push(ch);
+                }
+                if self.input.peek() == Some(&'.') {
+                    number.push(self.input.next().unwrap());
+                    while let Some(ch) = self.input.next_if(|c| c.is_ascii_digit()) {
+                        number.push(ch);
+                    }
+                }
+                Ok(Some(number))
+            }
             // Swallow whitespace.
             Some(x) if x.is_whitespace() => {
                 while let Some(ws) = self.input.peek() {
@@ -84,7 +191,7 @@ impl<I: Iterator<Item = char>> EbnfTokenizer<I> {
     }
 }
 
-impl<I: Iterator<Item = char>> Iterator for EbnfTokenizer<I> {
+impl<I: Iterator<Item = char> + Clone> Iterator for EbnfTokenizer<I> {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -137,4 +244,66 @@ mod tests {
             assert_eq!(token, expected[idx]);
         }
     }
+
+    #[test]
+    fn precedence() {
+        let input = "e := e '+' e %left 10 | n;";
+        let expected = vec![
+            "e", ":=", "e", "'", "+", "'", "e", "%left", "10", "|", "n", ";",
+        ];
+        let tokens: Vec<_> = EbnfTokenizer::new(input.chars()).collect();
+        assert_eq!(tokens, expected);
+        let input = "e := n %weight 0.25 | n n %weight 2 %left 1;";
+        let expected = vec![
+            "e", ":=", "n", "%weight", "0.25", "|", "n", "n", "%weight", "2", "%left", "1", ";",
+        ];
+        let tokens: Vec<_> = EbnfTokenizer::new(input.chars()).collect();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn patterns() {
+        let input = r"n := [0-9]+ | [^]\]]* [ /[a-z\/]+/ ] [-+]? ['a'] [x|y] [z];";
+        let expected = vec![
+            "n",
+            ":=",
+            "[0-9]+",
+            "|",
+            "[^]\\]]*",
+            "[",
+            "/[a-z\\/]+/",
+            "]",
+            "[-+]?",
+            "[",
+            "'",
+            "a",
+            "'",
+            "]",
+            "[",
+            "x",
+            "|",
+            "y",
+            "]",
+            "[",
+            "z",
+            "]",
+            ";",
+        ];
+        let tokens: Vec<_> = EbnfTokenizer::new(input.chars()).collect();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn repetition() {
+        let input = "a := b* c+ (d)? e{2} f{2,} g{0,3} {h};";
+        let expected = vec![
+            "a", ":=", "b", "*", "c", "+", "(", "d", ")", "?", "e", "{2}", "f", "{2,}", "g",
+            "{0,3}", "{", "h", "}", ";",
+        ];
+        let tokens: Vec<_> = EbnfTokenizer::new(input.chars()).collect();
+        assert_eq!(tokens, expected);
+        // Bad counts stop the tokenizer
+        let tokens: Vec<_> = EbnfTokenizer::new("a{3,2}".chars()).collect();
+        assert_eq!(tokens, vec!["a"]);
+    }
 }
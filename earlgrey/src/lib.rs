@@ -1,14 +1,18 @@
 #![deny(warnings)]
 
 mod earley;
-pub use earley::{EarleyForest, EarleyParser, Grammar, GrammarBuilder};
+pub use earley::{
+    Assoc, Derivation, EarleyForest, EarleyParser, Grammar, GrammarBuilder, Lint, MaybeSendSync,
+    PackedNode, ParseError, ParseObserver, ParseSession, ParseStats, PartialParse, Pattern, Rule,
+    RuleStats, SemanticGrammarBuilder, SentenceGenerator, Sppf, SppfLabel, SppfNode,
+};
 
 mod ebnf;
 mod ebnf_tokenizer;
 pub use ebnf::EbnfGrammarParser;
 
 mod parsers;
-pub use parsers::{sexpr_parser, Sexpr};
+pub use parsers::{LabelledSexpr, Sexpr, labelled_sexpr_parser, sexpr_parser};
 
 #[cfg(test)]
 mod ebnf_test;
@@ -1,7 +1,7 @@
 #![deny(warnings)]
 
-use crate::earley::{EarleyForest, EarleyParser, Grammar};
-use std::fmt::Debug;
+use crate::earley::{EarleyForest, EarleyParser, Grammar, dot_string, is_identifier, json_string};
+use std::fmt::{Debug, Write};
 
 #[derive(Clone, Debug)]
 pub enum Sexpr {
@@ -39,6 +39,124 @@ impl Sexpr {
             }
         }
     }
+
+    /// Graphviz DOT of the tree. Lists are drawn as points and atoms as
+    /// their lexeme, empty lists as epsilon.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph sexpr {\n");
+        self.dot_helper(&mut 0, &mut out);
+        out += "}\n";
+        out
+    }
+
+    // Write the node and its children, returns the id of the node
+    fn dot_helper(&self, next_id: &mut usize, out: &mut String) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+        match self {
+            Sexpr::Atom(lexeme) => writeln!(
+                out,
+                "  n{} [shape=plaintext, label={}];",
+                id,
+                dot_string(lexeme)
+            )
+            .unwrap(),
+            Sexpr::List(subn) if subn.is_empty() => {
+                writeln!(out, "  n{} [shape=plaintext, label=\"\u{03b5}\"];", id).unwrap()
+            }
+            Sexpr::List(subn) => {
+                writeln!(out, "  n{} [shape=point];", id).unwrap();
+                for child in subn {
+                    let child = child.dot_helper(next_id, out);
+                    writeln!(out, "  n{} -> n{};", id, child).unwrap();
+                }
+            }
+        }
+        id
+    }
+
+    /// Serialize the tree as JSON. Atoms are strings and Lists arrays.
+    pub fn to_json(&self) -> String {
+        match self {
+            Sexpr::Atom(lexeme) => json_string(lexeme),
+            Sexpr::List(subn) => {
+                let subn: Vec<_> = subn.iter().map(Sexpr::to_json).collect();
+                format!("[{}]", subn.join(", "))
+            }
+        }
+    }
+}
+
+/// A parse tree labelled with grammar symbols. Unlike `Sexpr` every rule
+/// makes a List, even rules with a single symbol.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LabelledSexpr {
+    // Terminal and lexeme, eg: ("num", "3")
+    Atom(String, String),
+    // Rule head and the trees of its symbols, eg: ("expr", [...])
+    List(String, Vec<LabelledSexpr>),
+}
+
+impl LabelledSexpr {
+    /// Indented S-expression. Lists whose children are all Atoms fit in a
+    /// line, eg: `(sum (n "1") ("+" "+") (n "2"))`, others put each child in
+    /// a line of its own. Names other than plain identifiers are quoted.
+    pub fn to_sexpr(&self) -> String {
+        let mut out = String::new();
+        self.sexpr_helper(0, &mut out);
+        out
+    }
+
+    fn sexpr_helper(&self, indent: usize, out: &mut String) {
+        match self {
+            LabelledSexpr::Atom(terminal, lexeme) => {
+                write!(out, "({} {})", sexpr_name(terminal), json_string(lexeme)).unwrap()
+            }
+            LabelledSexpr::List(head, children) => {
+                write!(out, "({}", sexpr_name(head)).unwrap();
+                let flat = children
+                    .iter()
+                    .all(|c| matches!(c, LabelledSexpr::Atom(_, _)));
+                for child in children {
+                    if flat {
+                        *out += " ";
+                    } else {
+                        write!(out, "\n{}", "  ".repeat(indent + 1)).unwrap();
+                    }
+                    child.sexpr_helper(indent + 1, out);
+                }
+                *out += ")";
+            }
+        }
+    }
+
+    /// Serialize the tree as JSON. Atoms are `{"terminal", "lexeme"}`
+    /// objects and Lists `{"head", "children"}`.
+    pub fn to_json(&self) -> String {
+        match self {
+            LabelledSexpr::Atom(terminal, lexeme) => format!(
+                r#"{{"terminal": {}, "lexeme": {}}}"#,
+                json_string(terminal),
+                json_string(lexeme)
+            ),
+            LabelledSexpr::List(head, children) => {
+                let children: Vec<_> = children.iter().map(LabelledSexpr::to_json).collect();
+                format!(
+                    r#"{{"head": {}, "children": [{}]}}"#,
+                    json_string(head),
+                    children.join(", ")
+                )
+            }
+        }
+    }
+}
+
+// Quote names that could be taken for parens or lexemes, eg: `(` or `<Uniq-1>`
+fn sexpr_name(name: &str) -> String {
+    match is_identifier(name) {
+        true => name.to_string(),
+        false => json_string(name),
+    }
 }
 
 pub fn sexpr_parser<InputIter>(
@@ -50,7 +168,7 @@ where
 {
     let mut tree_builder = EarleyForest::new(|_, tok| Sexpr::Atom(tok.to_string()));
 
-    for rule in &grammar.rules {
+    for rule in grammar.rules() {
         tree_builder.action(&rule.to_string(), move |mut nodes| match nodes.len() {
             1 => nodes.swap_remove(0),
             _ => Sexpr::List(nodes),
@@ -60,3 +178,108 @@ where
     let parser = EarleyParser::new(grammar);
     Ok(move |tokenizer| tree_builder.eval_all(&parser.parse(tokenizer)?))
 }
+
+/// Like `sexpr_parser` but trees keep the rule heads and terminal names
+pub fn labelled_sexpr_parser<InputIter>(
+    grammar: Grammar,
+) -> Result<impl Fn(InputIter) -> Result<Vec<LabelledSexpr>, String>, String>
+where
+    InputIter: Iterator,
+    InputIter::Item: AsRef<str> + std::fmt::Debug,
+{
+    let mut tree_builder = EarleyForest::new(|terminal, tok| {
+        LabelledSexpr::Atom(terminal.to_string(), tok.to_string())
+    });
+
+    for rule in grammar.rules() {
+        let head = rule.head.clone();
+        tree_builder.action(&rule.to_string(), move |nodes| {
+            LabelledSexpr::List(head.clone(), nodes)
+        });
+    }
+
+    let parser = EarleyParser::new(grammar);
+    Ok(move |tokenizer| tree_builder.eval_all(&parser.parse(tokenizer)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LabelledSexpr, Sexpr, labelled_sexpr_parser, sexpr_parser};
+    use crate::earley::{Grammar, GrammarBuilder};
+
+    fn grammar_sum() -> Grammar {
+        GrammarBuilder::default()
+            .nonterm("E")
+            .terminal("n", |n| n.parse::<u32>().is_ok())
+            .terminal("+", |n| n == "+")
+            .rule("E", &["E", "+", "n"])
+            .rule("E", &["n"])
+            .into_grammar("E")
+            .unwrap()
+    }
+
+    fn parse_sum(input: &str) -> Sexpr {
+        let parser = sexpr_parser(grammar_sum()).unwrap();
+        parser(input.split_whitespace()).unwrap().remove(0)
+    }
+
+    #[test]
+    fn sexpr_to_json() {
+        let tree = parse_sum("1 + 2 + 3");
+        assert_eq!(tree.to_json(), r#"[["1", "+", "2"], "+", "3"]"#);
+        let quoted = Sexpr::List(vec![Sexpr::Atom("\"a\"".to_string()), Sexpr::List(vec![])]);
+        assert_eq!(quoted.to_json(), r#"["\"a\"", []]"#);
+    }
+
+    #[test]
+    fn sexpr_to_dot() {
+        let dot = parse_sum("1 + 2").to_dot();
+        let expected = r#"digraph sexpr {
+  n0 [shape=point];
+  n1 [shape=plaintext, label="1"];
+  n0 -> n1;
+  n2 [shape=plaintext, label="+"];
+  n0 -> n2;
+  n3 [shape=plaintext, label="2"];
+  n0 -> n3;
+}
+"#;
+        assert_eq!(dot, expected);
+    }
+
+    #[test]
+    fn labelled_sexpr() {
+        let parser = labelled_sexpr_parser(grammar_sum()).unwrap();
+        let tree = parser("1 + 2".split_whitespace()).unwrap().remove(0);
+        let atom = |t: &str, l: &str| LabelledSexpr::Atom(t.to_string(), l.to_string());
+        let expected = LabelledSexpr::List(
+            "E".to_string(),
+            vec![
+                LabelledSexpr::List("E".to_string(), vec![atom("n", "1")]),
+                atom("+", "+"),
+                atom("n", "2"),
+            ],
+        );
+        assert_eq!(tree, expected);
+        let sexpr = r#"(E
+  (E (n "1"))
+  ("+" "+")
+  (n "2"))"#;
+        assert_eq!(tree.to_sexpr(), sexpr);
+        let helper = LabelledSexpr::List(
+            "<Uniq-1>".to_string(),
+            vec![atom("(", "("), atom("/[0-9]+/", "3")],
+        );
+        assert_eq!(
+            helper.to_sexpr(),
+            r#"("<Uniq-1>" ("(" "(") ("/[0-9]+/" "3"))"#
+        );
+        let json = concat!(
+            r#"{"head": "E", "children": ["#,
+            r#"{"head": "E", "children": [{"terminal": "n", "lexeme": "1"}]}, "#,
+            r#"{"terminal": "+", "lexeme": "+"}, "#,
+            r#"{"terminal": "n", "lexeme": "2"}]}"#
+        );
+        assert_eq!(tree.to_json(), json);
+    }
+}
@@ -0,0 +1,59 @@
+// Benchmark parsing time expressions with the fluxcap grammar.
+// Run with: cargo run --release -p fluxcap --example time_bench
+use std::time::Instant;
+
+const EXPRESSIONS: &[&str] = &[
+    "next monday",
+    "tue after next",
+    "the 3rd day of the 2nd week of may",
+    "third tuesday of the month after next",
+    "the 2nd day of the 3rd week of 1987",
+    "days between mar and apr",
+    "years since mar 13th 2005",
+    "2 weeks ago",
+];
+
+const TEXT: &str = "remind me next friday at the office, then on the 3rd day \
+    of the 2nd week of may and also two days after the last day of feb 2020";
+
+fn main() {
+    let parser = fluxcap::time_parser();
+    let tokenize = |text: &'static str| -> Vec<&str> {
+        text.split(&[' ', ','][..])
+            .filter(|w| !w.is_empty())
+            .collect()
+    };
+
+    let rounds = 200;
+    let start = Instant::now();
+    for _ in 0..rounds {
+        for expression in EXPRESSIONS {
+            parser
+                .parse(tokenize(expression).into_iter())
+                .expect("Parse failed");
+        }
+    }
+    let parse_time = start.elapsed();
+    println!(
+        "{:>5} x {} expressions: parse {:>10.2?}  ({:.2?} each)",
+        rounds,
+        EXPRESSIONS.len(),
+        parse_time,
+        parse_time / (rounds * EXPRESSIONS.len() as u32)
+    );
+
+    let rounds = 20;
+    let tokens = tokenize(TEXT);
+    let start = Instant::now();
+    for _ in 0..rounds {
+        assert!(!parser.parse_substrings(tokens.iter()).is_empty());
+    }
+    let extract_time = start.elapsed();
+    println!(
+        "{:>5} x {} tokens of text: substrings {:>10.2?}  ({:.2?} each)",
+        rounds,
+        tokens.len(),
+        extract_time,
+        extract_time / rounds
+    );
+}
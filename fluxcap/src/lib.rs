@@ -2,7 +2,7 @@
 
 mod constants;
 mod time_parser;
-pub use time_parser::time_grammar;
+pub use time_parser::{time_grammar, time_parser};
 pub use time_parser::debug_time_expression;
 
 mod time_semantics;
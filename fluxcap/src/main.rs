@@ -13,7 +13,7 @@ fn main() -> Result<(), String> {
     } else {
         std::env::args()
             .skip(1)
-            .filter(|arg| arg != "-v")
+            .filter(|arg| arg != "-v" && arg != "--dot")
             .collect::<Vec<String>>()
             .join(" ")
     };
@@ -46,11 +46,12 @@ fn main() -> Result<(), String> {
     }
 
     let verbose = std::env::args().any(|arg| arg == "-v");
-    if verbose {
+    let dot = std::env::args().any(|arg| arg == "--dot");
+    if verbose || dot {
         match fluxcap::debug_time_expression(&input) {
             Err(error) => eprintln!("{}", error),
             Ok(trees) => for t in trees {
-                println!("{}", t.print());
+                println!("{}", if dot { t.to_dot() } else { t.print() });
             }
         }
     }
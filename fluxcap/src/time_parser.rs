@@ -67,16 +67,18 @@ pub fn time_grammar() -> &'static str {
 fn _grammar() -> Result<earlgrey::Grammar, String> {
     use std::str::FromStr;
     use crate::constants::*;
+    // Terminals capture their value so semantic actions needn't parse again
+    let any_ordinal = |d: &str| ordinal(d).or_else(|| short_ordinal(d)).map(|n| n as i32);
     earlgrey::EbnfGrammarParser::new(time_grammar(), "time")
-        .plug_terminal("ordinal", |d| ordinal(d).or_else(|| short_ordinal(d)).is_some())
-        .plug_terminal("day_ordinal", |d| ordinal(d).or_else(|| short_ordinal(d)).is_some())
-        .plug_terminal("weekday", |d| weekday(d).is_some())
-        .plug_terminal("month", |d| month(d).is_some())
-        .plug_terminal("grain", |g| kronos::Grain::from_str(g).is_ok())
-        .plug_terminal("year", |y| if let Ok(year) = i32::from_str(y)
-                       { year > 999 && year < 2200 } else { false })
-        .plug_terminal("small_int", |u| if let Ok(u) = usize::from_str(u)
-                       { u < 100 } else { false })
+        .plug_terminal_capture("ordinal", any_ordinal)
+        .plug_terminal_capture("day_ordinal", any_ordinal)
+        .plug_terminal_capture("weekday", |d| weekday(d).map(|n| n as i32))
+        .plug_terminal_capture("month", |d| month(d).map(|n| n as i32))
+        .plug_terminal_capture("grain", |g| kronos::Grain::from_str(g).ok())
+        .plug_terminal_capture("year", |y| i32::from_str(y).ok()
+                               .filter(|year| *year > 999 && *year < 2200))
+        .plug_terminal_capture("small_int", |u| usize::from_str(u).ok()
+                               .filter(|u| *u > 0 && *u < 100).map(|u| u as i32))
         .into_grammar()
 }
 
@@ -5,6 +5,7 @@ type Date = chrono::NaiveDate;
 
 use earlgrey::{EarleyForest, EarleyParser};
 use kronos as k;
+use std::any::Any;
 type Shim = kronos::Shim<'static>;
 
 #[derive(Debug, PartialEq)]
@@ -138,20 +139,17 @@ impl TimeNode {
     }
 }
 
-fn terminal_eval() -> impl Fn(&str, &str) -> TimeNode {
-    use crate::constants::*;
-    use std::str::FromStr;
+// Lift the values captured by terminals (see time_parser), keywords are Nop
+fn terminal_eval() -> impl Fn(&str, &str, &dyn Any) -> TimeNode {
     use TimeNode::*;
-    |terminal, lex| match terminal {
-        "day_ordinal" | "ordinal" => {
-            Int(ordinal(lex).or_else(|| short_ordinal(lex)).unwrap() as i32)
+    |_, _, value| {
+        if let Some(n) = value.downcast_ref::<i32>() {
+            Int(*n)
+        } else if let Some(grain) = value.downcast_ref::<k::Grain>() {
+            Grain(*grain)
+        } else {
+            Nop
         }
-        "weekday" => Int(weekday(lex).unwrap() as i32),
-        "month" => Int(month(lex).unwrap() as i32),
-        "grain" => Grain(k::Grain::from_str(lex).unwrap()),
-        "year" => Int(i32::from_str(lex).unwrap()),
-        "small_int" => Int(i32::from_str(lex).unwrap()),
-        _ => Nop,
     }
 }
 
@@ -319,6 +317,22 @@ fn evaler_time(ev: &mut EarleyForest<'_, TimeNode>, reftime: DateTime) {
     });
 }
 
+// Point at the word where parsing failed, eg: "unexpected 'in', expected
+// one of `of`, `after` at word 3"
+fn parse_error(error: &earlgrey::ParseError) -> String {
+    let unexpected = match &error.lexeme {
+        Some(lexeme) => format!("unexpected '{}'", lexeme),
+        None => "unexpected end".to_string(),
+    };
+    let expected: Vec<_> = error.expected.iter().map(|t| format!("`{}`", t)).collect();
+    format!(
+        "{}, expected one of {} at word {}",
+        unexpected,
+        expected.join(", "),
+        error.position
+    )
+}
+
 pub struct TimeMachine<'a> {
     parser: EarleyParser,
     evaler: EarleyForest<'a, TimeNode>,
@@ -328,7 +342,7 @@ pub struct TimeMachine<'a> {
 impl<'a> TimeMachine<'a> {
     pub fn new(reftime: DateTime) -> TimeMachine<'a> {
         use crate::time_parser;
-        let mut evaler = EarleyForest::new(terminal_eval());
+        let mut evaler = EarleyForest::new_with_capture(terminal_eval());
         evaler_sequence(&mut evaler);
         evaler_comp_seq(&mut evaler);
         evaler_comp_grain(&mut evaler);
@@ -340,13 +354,18 @@ impl<'a> TimeMachine<'a> {
         }
     }
 
+    // Check semantic actions match the grammar rules (eg: typos)
+    pub fn validate(&self) -> Result<(), String> {
+        self.evaler.validate(&self.parser.grammar)
+    }
+
     pub fn eval(&self, time: &str) -> Result<Vec<TimeEl>, String> {
         let mut tokenizer = time.split(&[' ', ','][..]).filter(|w| !w.is_empty());
 
         let state = self
             .parser
             .parse(&mut tokenizer)
-            .map_err(|e| format!("TimeMachine {:?} for '{}'", e, time))?;
+            .map_err(|e| format!("TimeMachine {} for '{}'", parse_error(&e), time))?;
 
         Ok(self
             .evaler
@@ -356,4 +375,25 @@ impl<'a> TimeMachine<'a> {
             .map(|tree| tree.eval(self.reftime))
             .collect())
     }
+
+    // Find time expressions in free text, eg: 'remind me next friday at 5'
+    pub fn extract(&self, text: &str) -> Result<Vec<(String, Vec<TimeEl>)>, String> {
+        let tokens: Vec<_> = text
+            .split(&[' ', ','][..])
+            .filter(|w| !w.is_empty())
+            .collect();
+        let mut found = Vec::new();
+        for parse in self.parser.parse_substrings(tokens.iter()) {
+            let matched = tokens[parse.start..parse.end].join(" ");
+            let times = self
+                .evaler
+                .eval_all(&parse.trees)
+                .map_err(|e| format!("TimeMachine {:?} for '{}'", e, matched))?
+                .into_iter()
+                .map(|tree| tree.eval(self.reftime))
+                .collect();
+            found.push((matched, times));
+        }
+        Ok(found)
+    }
 }
@@ -125,3 +125,78 @@ fn t_shifts() -> Result<(), String> {
     assert_eq!(tm.eval("in a year")?, r(d(2017, 10, 26), dttm(2017, 10, 26, 0, 0, 1), g::Second));
     Ok(())
 }
+
+#[test]
+fn t_actions_match_grammar() {
+    let tm = TimeMachine::new(d(2016, 9, 5));
+    assert_eq!(tm.validate(), Ok(()));
+}
+
+#[test]
+fn t_extract() -> Result<(), String> {
+    let tm = TimeMachine::new(d(2016, 9, 5));
+    let found = tm.extract("remind me next friday at the office")?;
+    assert_eq!(found, vec![("next friday".to_string(), r(d(2016, 9, 9), d(2016, 9, 10), g::Day))]);
+    let found = tm.extract("lunch on monday or next friday")?;
+    let found: Vec<_> = found.into_iter().map(|(text, _)| text).collect();
+    assert_eq!(found, vec!["on monday", "next friday"]);
+    assert_eq!(tm.extract("nothing to see here")?, vec![]);
+    Ok(())
+}
+
+#[test]
+fn t_symbols() {
+    // Parse parts of a time expression, no need for a grammar per symbol
+    let parser = crate::time_parser::time_parser();
+    let parses = |symbol, text: &str| parser.parse_symbol(symbol, text.split_whitespace()).is_ok();
+    assert!(parses("comp_grain", "2 days and a week"));
+    assert!(parses("named_seq", "monday 3rd of march"));
+    assert!(!parses("named_seq", "next monday"));
+    assert!(parses("time", "next monday"));
+}
+
+#[test]
+fn t_fuzz() -> Result<(), String> {
+    let parser = crate::time_parser::time_parser();
+    let generator = earlgrey::SentenceGenerator::new(&parser.grammar)
+        .samples("ordinal", &["first", "3rd"])
+        .samples("day_ordinal", &["second", "28th"])
+        .samples("weekday", &["monday", "sundays"])
+        .samples("month", &["march", "dec"])
+        .samples("grain", &["day", "week", "year"])
+        .samples("year", &["1999", "2016"])
+        .samples("small_int", &["1", "99"]);
+    let tm = TimeMachine::new(d(2016, 9, 5));
+    // Shallow sentences parse and evaluate. Except a year counted from
+    // now (eg: 'in 2 2016', 'a 1999 ago'), there's no such year and kronos
+    // panics looking for it.
+    let relative_year = |s: &[String]| {
+        let year = s.iter().any(|w| w == "1999" || w == "2016");
+        year && (s[0] == "in" || s.last().is_some_and(|w| w == "ago"))
+    };
+    let shallow = generator.exhaustive("time", 2)?;
+    for sentence in shallow.iter().filter(|s| !relative_year(s)) {
+        let sentence = sentence.join(" ");
+        assert!(tm.eval(&sentence).is_ok(), "{}", sentence);
+    }
+    // Deeper sentences nest sequences kronos can't always evaluate, it
+    // panics or never ends (eg: 'the 2nd day of the day'). Only parse them.
+    for _ in 0..500 {
+        let sentence = generator.random("time", 6)?;
+        assert!(parser.parse(sentence.iter()).is_ok(), "{:?}", sentence);
+    }
+    // Found fuzzing, used to underflow
+    assert!(tm.eval("in 0 mondays").is_err());
+    assert!(tm.eval("0 mondays ago").is_err());
+    Ok(())
+}
+
+#[test]
+fn t_parse_error() {
+    let tm = TimeMachine::new(d(2016, 9, 5));
+    let error = tm.eval("friday after monday in march").unwrap_err();
+    let expected = "TimeMachine unexpected 'monday', expected one of `next` at word 2";
+    assert_eq!(error, format!("{} for 'friday after monday in march'", expected));
+    let error = tm.eval("3rd monday of").unwrap_err();
+    assert!(error.starts_with("TimeMachine unexpected end, expected one of `day_ordinal`"));
+}
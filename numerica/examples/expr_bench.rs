@@ -0,0 +1,56 @@
+// Benchmark parsing expressions with the numerica grammar.
+// Run with: cargo run --release -p numerica --example expr_bench
+use std::time::Instant;
+
+fn sum(len: usize) -> String {
+    let terms: Vec<_> = (0..len).map(|i| format!("x{} * {}", i, i)).collect();
+    terms.join(" + ")
+}
+
+fn nested(depth: usize) -> String {
+    let mut expr = "x".to_string();
+    for i in 0..depth {
+        expr = format!("f[{}, ({} - {})^2]", expr, i, expr.len());
+    }
+    expr
+}
+
+fn main() {
+    let parser = numerica::parser().expect("Bad grammar");
+    let small = [
+        "1 + 2 * 3",
+        "a = {1, 2, {3, 4}}",
+        "Sin[x]^2 + Cos[x]^2 /. x -> 3",
+        "-x! ~ 2 / (y + 1)",
+    ];
+
+    let rounds = 500;
+    let start = Instant::now();
+    for _ in 0..rounds {
+        for input in small {
+            parser(input).expect("Parse failed");
+        }
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:>5} x {} small expressions: {:>10.2?}",
+        rounds,
+        small.len(),
+        elapsed
+    );
+
+    for (name, input) in [("sum", sum(10)), ("sum", sum(20)), ("nested", nested(3))] {
+        let rounds = 5;
+        let start = Instant::now();
+        for _ in 0..rounds {
+            parser(&input).expect("Parse failed");
+        }
+        let elapsed = start.elapsed() / rounds;
+        println!(
+            "{:>6} of {:>5} chars: {:>10.2?}",
+            name,
+            input.len(),
+            elapsed
+        );
+    }
+}
@@ -237,7 +237,8 @@ pub fn parser() -> Result<impl Fn(&str) -> Result<Expr, String>, String> {
     let parser = earlgrey::EarleyParser::new(numerica_grammar()?);
     Ok(move |input: &str| {
         let tokenizer = crate::tokenizer::Tokenizer::new(input.chars());
-        let mut trees = evaler.eval_all_recursive(&parser.parse(tokenizer)?)?;
+        let trees = parser.parse(tokenizer).map_err(|e| parse_error(&e))?;
+        let mut trees = evaler.eval_all_recursive(&trees)?;
         if trees.len() > 1 {
             for t in &trees {
                 eprintln!("{:?}", t);
@@ -252,6 +253,22 @@ pub fn parser() -> Result<impl Fn(&str) -> Result<Expr, String>, String> {
     })
 }
 
+// Point at the token where parsing failed, eg: "unexpected ']', expected
+// one of `(`, `number` at token 4"
+fn parse_error(error: &earlgrey::ParseError) -> String {
+    let unexpected = match &error.lexeme {
+        Some(lexeme) => format!("unexpected '{}'", lexeme),
+        None => "unexpected end".to_string(),
+    };
+    let expected: Vec<_> = error.expected.iter().map(|t| format!("`{}`", t)).collect();
+    format!(
+        "{}, expected one of {} at token {}",
+        unexpected,
+        expected.join(", "),
+        error.position
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::parser;
@@ -289,4 +306,39 @@ mod tests {
         assert_eq!(parser()?(input)?, expected);
         Ok(())
     }
+
+    #[test]
+    fn fuzz_expr() -> Result<(), std::string::String> {
+        // Random sentences of the grammar survive the tokenizer, parse and
+        // convert into an Expr
+        let grammar = super::numerica_grammar()?;
+        let generator = earlgrey::SentenceGenerator::new(&grammar)
+            .samples("string", &["a b", ""])
+            .samples("symbol", &["x", "Sum", "a_1"])
+            .samples("number", &["0", "2.5"]);
+        let parser = parser()?;
+        for _ in 0..300 {
+            let sentence = generator.random("expr", 14)?.join(" ");
+            assert!(parser(&sentence).is_ok(), "{}", sentence);
+        }
+        // Found fuzzing, the tokenizer didn't know about '%'
+        let expected = Expr::from_head("Mod", vec![Symbol("x".into()), Number(2.0)]);
+        assert_eq!(parser("x % 2")?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_error() -> Result<(), std::string::String> {
+        let parser = parser()?;
+        let expected = "`\"`, `(`, `-`, `number`, `symbol`, `{`";
+        assert_eq!(
+            parser("f[x,]").unwrap_err(),
+            format!("unexpected ']', expected one of {} at token 4", expected)
+        );
+        assert_eq!(
+            parser("1 +").unwrap_err(),
+            format!("unexpected end, expected one of {} at token 2", expected)
+        );
+        Ok(())
+    }
 }
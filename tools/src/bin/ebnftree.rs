@@ -0,0 +1,55 @@
+// Parse inputs with an EBNF grammar and print their labelled parse trees.
+// Usage: ebnftree <grammar-file> <start-symbol> [--json] [input...]
+// Tokens are separated by whitespace. Without input args each line of
+// stdin is parsed on its own. Exits with status 1 if any input fails.
+use std::io::BufRead;
+
+fn usage() -> String {
+    "Usage: ebnftree <grammar-file> <start-symbol> [--json] [input...]".to_string()
+}
+
+fn main() -> Result<(), String> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let json = args.iter().any(|arg| arg == "--json");
+    args.retain(|arg| arg != "--json");
+    if args.len() < 2 {
+        return Err(usage());
+    }
+
+    let grammar = std::fs::read_to_string(&args[0]).map_err(|e| format!("{}: {}", args[0], e))?;
+    let grammar = earlgrey::EbnfGrammarParser::new(&grammar, &args[1]).into_grammar()?;
+    let parser = earlgrey::labelled_sexpr_parser(grammar)?;
+
+    let print = |input: &str| {
+        let tokens: Vec<String> = input.split_whitespace().map(String::from).collect();
+        match parser(tokens.into_iter()) {
+            Err(e) => {
+                eprintln!("{}", e);
+                false
+            }
+            Ok(trees) => {
+                for tree in trees {
+                    if json {
+                        println!("{}", tree.to_json());
+                    } else {
+                        println!("{}", tree.to_sexpr());
+                    }
+                }
+                true
+            }
+        }
+    };
+
+    let mut failed = false;
+    if args.len() > 2 {
+        failed = !print(&args[2..].join(" "));
+    } else {
+        for line in std::io::stdin().lock().lines() {
+            failed |= !print(&line.map_err(|e| e.to_string())?);
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}